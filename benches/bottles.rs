@@ -0,0 +1,51 @@
+//! interpreter benchmark on a 99-bottles-style workload
+//!
+//! ```sh
+//! cargo bench --bench bottles
+//! ```
+
+use frust::*;
+use std::time::Instant;
+
+const BOTTLES: &str = include_str!("../tests/99bottles.fr");
+
+/// a VM with all words, writing to a sink
+fn vm() -> VM {
+    let mut vm = VM::new_null();
+    builtins::add_all(&mut vm.dictionary);
+    vm
+}
+
+/// runs `code` `runs` times and prints the time per run
+fn bench(name: &str, vm: &mut VM, code: &str, runs: u32) {
+    // warm up
+    vm.eval(code).expect("benchmark code");
+    let start = Instant::now();
+    for _ in 0..runs {
+        vm.eval(code).expect("benchmark code");
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<12} {:>10.3?} per run, {} runs in {:.3?}",
+        name,
+        elapsed / runs,
+        runs,
+        elapsed
+    );
+}
+
+fn main() {
+    // the song, without the line that sings it
+    let mut bottles = vm();
+    for line in BOTTLES.lines().filter(|line| !line.starts_with("99")) {
+        bottles.eval(line).expect("99 bottles definitions");
+    }
+    bench("99 bottles", &mut bottles, "99 VERSES", 500);
+
+    // a tight loop calling a small word
+    let mut calls = vm();
+    calls
+        .eval(": inc 1 + ; : count 0 swap 0 do inc loop ;")
+        .unwrap();
+    bench("calls", &mut calls, "100000 count drop", 20);
+}
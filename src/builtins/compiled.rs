@@ -6,11 +6,8 @@ use crate::*;
 ///
 /// ```
 /// # use frust::*;
-/// # use std::time::Duration;
-/// # use std::sync::mpsc::channel;
-/// # let (test_writer, test_stdout) = channel();
-/// let mut vm = VM::new_null();
-/// # vm.write = Box::new( move |str: &str|  {test_writer.send(str.to_owned());});
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
 ///
 /// vm.dictionary.add("if",Cell::Compiler(builtins::compiletime_if));
 /// vm.dictionary.add(".\"",Cell::Compiler(builtins::compiletime_dot_q));
@@ -18,10 +15,27 @@ use crate::*;
 ///
 /// vm.eval(": foo IF . ELSE .\" No more \" THEN ; ");
 /// vm.eval(" 1 1 foo ");
-/// assert_eq!(test_stdout.recv_timeout(Duration::from_millis(400)).unwrap(), "1");
+/// assert_eq!(output.take(), "1 ");
 /// vm.eval(" 0 foo ");
-/// assert_eq!(test_stdout.recv_timeout(Duration::from_millis(400)).unwrap(), "No more");
-
+/// assert_eq!(output.take(), "No more");
+/// ```
+///
+/// branches carry their offsets, nothing but the flag
+/// is taken from the stack
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+///
+/// vm.eval(": sign if 1 else -1 then ; ");
+/// let sign = vm.dictionary.get("sign").unwrap();
+/// assert_eq!(sign[..], [Cell::ControlBranchIfZero(3), Cell::Data(Variable::Int(1)),
+///     Cell::ControlBranch(2), Cell::Data(Variable::Int(-1))]);
+///
+/// vm.eval(" 5 0 sign ");
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(-1)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(5)));
 /// ```
 pub fn compiletime_if(vm: &mut VM) -> Result<Vec<Cell>> {
     let mut branch_true: Vec<Cell> = Vec::new();
@@ -41,26 +55,36 @@ pub fn compiletime_if(vm: &mut VM) -> Result<Vec<Cell>> {
     }
 
     if next_token == "then" {
-        let program: Vec<Cell> = vec![
-            Cell::Data(Variable::Int(branch_true.len() as i64 + 3)),
-            Cell::ControlBranchIfZero,
-        ]
-        .into_iter()
-        .chain(branch_true.into_iter())
-        .chain(
-            vec![
-                Cell::Data(Variable::Int(branch_false.len() as i64 + 1)),
-                Cell::ControlBranch,
-            ]
-            .into_iter(),
-        )
-        .chain(branch_false.into_iter())
-        .collect();
+        let program: Vec<Cell> = vec![Cell::ControlBranchIfZero(branch_true.len() as i64 + 2)]
+            .into_iter()
+            .chain(branch_true)
+            .chain(vec![Cell::ControlBranch(branch_false.len() as i64 + 1)])
+            .chain(branch_false)
+            .collect();
         return Ok(program);
     }
     Err(Error::Compiler(vec![], next_token))
 }
 
+/// forth `(` comment compiletime evaluation
+///
+/// https://forth-standard.org/standard/core/p
+///
+/// drops the comment, so it works inside and outside of definitions
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+///
+/// vm.eval(": foo ( n -- n n ) dup ; ( a comment ) 1 foo");
+/// assert_eq!(vm.value_stack.len(), 2);
+/// ```
+pub fn compiletime_icomment(vm: &mut VM) -> Result<Vec<Cell>> {
+    super::icomment(vm)?;
+    Ok(vec![])
+}
+
 /// forth `."` command compiletime evaluation
 ///
 /// https://forth-standard.org/standard/core/Dotq
@@ -71,11 +95,8 @@ pub fn compiletime_if(vm: &mut VM) -> Result<Vec<Cell>> {
 ///
 /// ```
 /// # use frust::*;
-/// # use std::time::Duration;
-/// # use std::sync::mpsc::channel;
-/// # let (test_writer, test_stdout) = channel();
-/// let mut vm = VM::new_null();
-/// # vm.write = Box::new( move |str: &str|  {test_writer.send(str.to_owned());});
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
 ///
 /// vm.dictionary.add(".\"",Cell::Compiler(builtins::compiletime_dot_q));
 /// vm.dictionary.add(".", Cell::Exec(builtins::dot));
@@ -83,88 +104,115 @@ pub fn compiletime_if(vm: &mut VM) -> Result<Vec<Cell>> {
 ///
 /// vm.eval(": foo .\" bar baz \" 1 1 + ; ");
 /// vm.eval(" foo ");
-/// assert_eq!(test_stdout.recv_timeout(Duration::from_millis(400)).unwrap(), "bar baz");
+/// assert_eq!(output.take(), "bar baz");
+///
+/// vm.eval(": qux .\" bar baz\" ; ");
+/// vm.eval(" qux ");
+/// assert_eq!(output.take(), "bar baz");
 /// vm.eval(" . ");
-/// assert_eq!(test_stdout.recv_timeout(Duration::from_millis(400)).unwrap(), "2");
+/// assert_eq!(output.take(), "2 ");
 /// ```
 pub fn compiletime_dot_q(vm: &mut VM) -> Result<Vec<Cell>> {
+    Ok(vec![Cell::Print(parse_string(vm)?.into())])
+}
+
+/// collect all tokens up to the closing `"` into one string
+///
+/// tokens are joined with a single space
+fn parse_string(vm: &mut VM) -> Result<String> {
     let mut buffer = String::new();
     while let Some(token) = vm.input_buffer.pop_front() {
         if token.ends_with("\"") && token.len() > 1 {
+            if !buffer.is_empty() {
+                buffer.push(' ');
+            }
             buffer.push_str(token.trim_end_matches('"'));
         }
 
         if token.ends_with("\"") {
-            let comment = Cell::Data(Variable::from(buffer.as_str()));
-            let entry = Cell::Exec(runtime_dot_q);
-            return Ok(vec![comment, entry]);
+            return Ok(buffer);
         }
-        if buffer.len() > 0 {
-            buffer.push_str(" ");
+        if !buffer.is_empty() {
+            buffer.push(' ');
         }
         buffer.push_str(&token);
     }
-    return Err(Error::Parser("EOL".to_owned()));
+    Err(Error::Parser("EOL".to_owned()))
 }
 
-pub fn runtime_dot_q(vm: &mut VM) -> Result<()> {
-    let comment = vm.value_stack.pop()?;
-    (vm.write)(&format!("{}", comment));
-    Ok(())
+/// forth `s"` command compiletime evaluation
+///
+/// https://forth-standard.org/standard/core/Sq
+///
+/// collect everything between s" " into a string.
+/// we have no memory, so the string itself is pushed
+/// in place of the standard `c-addr`, followed by its length.
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+///
+/// vm.dictionary.add("s\"",Cell::Compiler(builtins::compiletime_s_quote));
+///
+/// vm.eval(": foo s\" bar baz\" ; ");
+/// vm.eval(" foo ");
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(7)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::from("bar baz")));
+///
+/// vm.eval(" s\" interpreted\" ");
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(11)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::from("interpreted")));
+/// ```
+pub fn compiletime_s_quote(vm: &mut VM) -> Result<Vec<Cell>> {
+    let string = parse_string(vm)?;
+    let len = string.chars().count() as i64;
+    Ok(vec![
+        Cell::Data(Variable::from(string.as_str())),
+        Cell::Data(Variable::Int(len)),
+    ])
 }
 
 /// forth `DO"` command compiletime evaluation
 ///
 /// https://forth-standard.org/standard/core/DO
 ///
+/// the loop end carries the jump back to the start of the body,
+/// index and limit live on the return stack
 ///
 /// ```
 /// # use frust::*;
-/// # use std::sync::mpsc::channel;
-/// # let (test_writer, test_stdout) = channel();
-/// let mut vm = VM::new_null();
-/// # vm.write = Box::new( move |str: &str|  {test_writer.send(str.to_owned());});
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+/// builtins::add_core(&mut vm.dictionary);
 ///
-/// vm.dictionary.add(".\"",Cell::Compiler(builtins::compiletime_dot_q));
-/// vm.dictionary.add(".", Cell::Exec(builtins::dot));
-/// vm.dictionary.add("+", Cell::Exec(builtins::plus));
+/// vm.eval(": stars 0 do 42 emit loop ; ");
+/// vm.eval(" 3 stars ");
+/// assert_eq!(output.take(), "***");
+/// assert!(vm.value_stack.is_empty());
 ///
+/// let stars = vm.dictionary.get("stars").unwrap();
+/// assert_eq!(stars[1..], [Cell::Exec(builtins::runtime_do),
+///     Cell::Data(Variable::Int(42)), Cell::Exec(builtins::emit), Cell::ControlLoop(-2)]);
 /// ```
 pub fn compiletime_do(vm: &mut VM) -> Result<Vec<Cell>> {
     let mut branch = vec![Cell::Exec(runtime_do)];
 
     let compiled = vm.compile();
     if let Err(Error::Compiler(routine, token)) = compiled {
-        match token.to_lowercase().as_str() {
-            "loop" => {
-                let len = routine.len() as i64 + 2;
-                branch.extend(routine);
-                branch.push(Cell::Exec(runtime_loop));
-                branch.push(Cell::Data(Variable::Int(len * -1)));
-                branch.push(Cell::ControlBranchIfNotZero);
-                return Ok(branch);
-            }
-            "+loop" => {
-                let len = routine.len() as i64 + 2;
-                branch.extend(routine);
-                branch.push(Cell::Exec(runtime_plus_loop));
-                branch.push(Cell::Data(Variable::Int(len * -1)));
-                branch.push(Cell::ControlBranchIfNotZero);
-                return Ok(branch);
-            }
-            "-loop" => {
-                let len = routine.len() as i64 + 2;
-                branch.extend(routine);
-                branch.push(Cell::Exec(runtime_minus_loop));
-                branch.push(Cell::Data(Variable::Int(len * -1)));
-                branch.push(Cell::ControlBranchIfNotZero);
-                return Ok(branch);
-            }
+        // jump back to the first cell of the body
+        let back = -(routine.len() as i64);
+        let end = match token.to_lowercase().as_str() {
+            "loop" => Cell::ControlLoop(back),
+            "+loop" => Cell::ControlPlusLoop(back),
+            "-loop" => Cell::ControlMinusLoop(back),
             _ => return Err(Error::Compiler(branch, token)),
-        }
+        };
+        branch.extend(routine);
+        branch.push(end);
+        Ok(branch)
     } else {
-        return Err(Error::Compiler(compiled?, "EOL".to_owned()));
-    };
+        Err(Error::Compiler(compiled?, "EOL".to_owned()))
+    }
 }
 
 pub fn runtime_do(vm: &mut VM) -> Result<()> {
@@ -175,46 +223,116 @@ pub fn runtime_do(vm: &mut VM) -> Result<()> {
     Ok(())
 }
 
-pub fn runtime_loop(vm: &mut VM) -> Result<()> {
-    let i_next = vm.return_stack.pop()? + variable::Variable::Int(1);
-    let limit = vm.return_stack.pop()?;
+/// a native with the source it stands for
+pub type Superinstruction = (&'static str, fn(&mut VM) -> Result<()>);
 
-    if i_next < limit {
-        vm.return_stack.push(limit);
-        vm.return_stack.push(i_next.clone());
-        vm.value_stack.push(1);
-    } else {
-        vm.value_stack.push(0);
-    }
+/// natives the optimizer puts in place of common pairs of cells,
+/// they are not words of their own
+pub const SUPERINSTRUCTIONS: [Superinstruction; 3] = [
+    ("dup +", runtime_two_star),
+    ("1 +", runtime_one_plus),
+    ("1 -", runtime_one_minus),
+];
+
+/// the source a superinstruction replaces, `None` for other natives
+pub fn superinstruction_name(word: fn(&mut VM) -> Result<()>) -> Option<&'static str> {
+    SUPERINSTRUCTIONS
+        .iter()
+        .find(|(_, native)| std::ptr::fn_addr_eq(*native, word))
+        .map(|(name, _)| *name)
+}
+
+/// runtime of `dup +` in optimized definitions
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::runtime_two_star;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(9);
+///
+/// runtime_two_star(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(18)));
+/// assert_eq!(runtime_two_star(&mut vm), Err(Error::Stack));
+/// ```
+pub fn runtime_two_star(vm: &mut VM) -> Result<()> {
+    let a = vm.value_stack.pop()?;
+    vm.value_stack.push(a.clone() + a);
     Ok(())
 }
 
-pub fn runtime_plus_loop(vm: &mut VM) -> Result<()> {
-    let offset = vm.value_stack.pop()?;
-    let i_next = vm.return_stack.pop()? + offset;
-    let limit = vm.return_stack.pop()?;
+/// runtime of `1 +` in optimized definitions
+pub fn runtime_one_plus(vm: &mut VM) -> Result<()> {
+    let a = vm.value_stack.pop()?;
+    vm.value_stack.push(a + Variable::Int(1));
+    Ok(())
+}
 
-    if i_next < limit {
-        vm.return_stack.push(limit);
-        vm.return_stack.push(i_next.clone());
-        vm.value_stack.push(1);
-    } else {
-        vm.value_stack.push(0);
-    }
+/// runtime of `1 -` in optimized definitions
+///
+/// unlike the word `1-` it fails on an empty stack, like `1 -`
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::runtime_one_minus;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(9);
+///
+/// runtime_one_minus(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(8)));
+/// assert_eq!(runtime_one_minus(&mut vm), Err(Error::Stack));
+/// ```
+pub fn runtime_one_minus(vm: &mut VM) -> Result<()> {
+    let a = vm.value_stack.pop()?;
+    vm.value_stack.push(a - Variable::Int(1));
     Ok(())
 }
 
-pub fn runtime_minus_loop(vm: &mut VM) -> Result<()> {
-    let offset = vm.value_stack.pop()?;
-    let i_next = vm.return_stack.pop()? - offset;
-    let limit = vm.return_stack.pop()?;
+/// forth `[char]` command compiletime evaluation
+///
+/// https://forth-standard.org/standard/core/BracketCHAR
+///
+/// takes the next token at compile time and compiles
+/// the character code of its first character as a literal
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+///
+/// vm.dictionary.add("[char]", Cell::Compiler(builtins::compiletime_bracket_char));
+/// vm.dictionary.add("emit", Cell::Exec(builtins::emit));
+///
+/// vm.eval(": star [CHAR] * emit ; ");
+/// vm.eval(" star ");
+/// assert_eq!(output.take(), "*");
+/// ```
+pub fn compiletime_bracket_char(vm: &mut VM) -> Result<Vec<Cell>> {
+    let token = vm
+        .input_buffer
+        .pop_front()
+        .ok_or(Error::Parser("EOL".to_owned()))?;
+    let first = token.chars().next().ok_or(Error::Parser(token.clone()))?;
+    Ok(vec![Cell::Data(Variable::Int(first as i64))])
+}
 
-    if i_next > limit {
-        vm.return_stack.push(limit);
-        vm.return_stack.push(i_next.clone());
-        vm.value_stack.push(1);
-    } else {
-        vm.value_stack.push(0);
-    }
-    Ok(())
+/// forth `recurse` command compiletime evaluation
+///
+/// https://forth-standard.org/standard/core/RECURSE
+///
+/// compiles a call of the definition that is being compiled
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+/// builtins::add_core(&mut vm.dictionary);
+///
+/// vm.eval(": countdown ?dup if dup . 1- recurse then ;");
+/// vm.eval("5 countdown");
+/// assert_eq!(output.take(), "5 4 3 2 1 ");
+/// ```
+pub fn compiletime_recurse(_: &mut VM) -> Result<Vec<Cell>> {
+    Ok(vec![Cell::Recurse])
 }
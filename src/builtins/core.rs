@@ -27,24 +27,23 @@ pub fn unimplemented(_: &mut VM) -> Result<()> {
 ///
 /// ```
 /// # use frust::*;
-/// # use std::sync::mpsc::channel;
 /// # use std::collections::VecDeque;
 /// # use frust::builtins::dot;
-/// # let (test_writer, test_stdout) = channel();
-/// let mut vm = VM::new_null();
-/// # vm.write = Box::new( move |str: &str|  {test_writer.send(str.to_owned());});
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
 ///
 /// vm.value_stack.push(23);
 ///
 /// assert_eq!(dot(&mut vm),Ok(()));
-/// assert_eq!(test_stdout.recv().unwrap(), "23");
+/// vm.flush();
+/// assert_eq!(output.take(), "23 ");
 ///
 /// assert_eq!(dot(&mut vm), Err(Error::Stack));
 ///
 /// ```
 pub fn dot(vm: &mut VM) -> Result<()> {
     let v = vm.value_stack.pop()?;
-    (vm.write)(&format!("{}", v));
+    vm.write(&format!("{} ", v))?;
     Ok(())
 }
 
@@ -196,6 +195,14 @@ pub fn min(vm: &mut VM) -> Result<()> {
     Ok(())
 }
 
+/// fails with `Error::DivisionByZero` if the top of the stack is `0`
+fn divisor(vm: &VM) -> Result<()> {
+    if vm.value_stack.at(0)? == &Variable::Int(0) {
+        return Err(Error::DivisionByZero);
+    }
+    Ok(())
+}
+
 /// forth `/` command
 ///
 /// https://forth-standard.org/standard/core/Div
@@ -214,10 +221,15 @@ pub fn min(vm: &mut VM) -> Result<()> {
 ///
 /// div(&mut vm);
 ///
-/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(4)))
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(4)));
 ///
+/// vm.value_stack.push(8);
+/// vm.value_stack.push(0);
+/// assert_eq!(div(&mut vm), Err(Error::DivisionByZero));
+/// assert_eq!(vm.value_stack.len(), 2);
 /// ```
 pub fn div(vm: &mut VM) -> Result<()> {
+    divisor(vm)?;
     let b = vm.value_stack.pop()?;
     let a = vm.value_stack.pop()?;
     vm.value_stack.push(a / b);
@@ -246,8 +258,12 @@ pub fn div(vm: &mut VM) -> Result<()> {
 /// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(4)));
 /// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
 ///
+/// vm.value_stack.push(9);
+/// vm.value_stack.push(0);
+/// assert_eq!(modulo(&mut vm), Err(Error::DivisionByZero));
 /// ```
 pub fn modulo(vm: &mut VM) -> Result<()> {
+    divisor(vm)?;
     let b = vm.value_stack.pop()?;
     let a = vm.value_stack.pop()?;
     vm.value_stack.push(a.clone() % b.clone());
@@ -431,6 +447,269 @@ pub fn over(vm: &mut VM) -> Result<()> {
     Ok(())
 }
 
+/// fails with `Error::Stack` unless the stack holds `n` values
+///
+/// checked up front, so words fail without touching the stack
+fn require(vm: &VM, n: usize) -> Result<()> {
+    if vm.value_stack.len() < n {
+        return Err(Error::Stack);
+    }
+    Ok(())
+}
+
+/// pops a stack index for `pick` and `roll`
+///
+/// the index has to point at a value below itself,
+/// otherwise the stack is left as it is
+fn stack_index(vm: &mut VM) -> Result<usize> {
+    let index = match vm.value_stack.at(0)? {
+        Variable::Int(index) => usize::try_from(*index).map_err(|_| Error::Stack)?,
+        _ => return Err(Error::Type),
+    };
+    require(vm, index.saturating_add(2))?;
+    vm.value_stack.pop()?;
+    Ok(index)
+}
+
+/// the forth `pick` command
+///
+/// https://forth-standard.org/standard/core/PICK
+///
+/// - pop `u`
+/// - push a copy of the value `u` places below the top
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::pick;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(1);
+/// vm.value_stack.push(2);
+/// vm.value_stack.push(1);
+///
+/// pick(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(2)));
+///
+/// vm.value_stack.push(5);
+/// assert_eq!(pick(&mut vm), Err(Error::Stack));
+/// assert_eq!(vm.value_stack.len(), 2);
+/// ```
+pub fn pick(vm: &mut VM) -> Result<()> {
+    let index = stack_index(vm)?;
+    let value = vm.value_stack.at(index)?.clone();
+    vm.value_stack.push(value);
+    Ok(())
+}
+
+/// the forth `roll` command
+///
+/// https://forth-standard.org/standard/core/ROLL
+///
+/// - pop `u`
+/// - move the value `u` places below the top to the top
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::roll;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(1);
+/// vm.value_stack.push(2);
+/// vm.value_stack.push(3);
+/// vm.value_stack.push(2);
+///
+/// roll(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(3)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(2)));
+/// ```
+pub fn roll(vm: &mut VM) -> Result<()> {
+    let index = stack_index(vm)?;
+    let value = vm.value_stack.remove(index)?;
+    vm.value_stack.push(value);
+    Ok(())
+}
+
+/// the forth `depth` command
+///
+/// https://forth-standard.org/standard/core/DEPTH
+///
+/// - push the number of values on the stack
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::depth;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(7);
+///
+/// depth(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// ```
+pub fn depth(vm: &mut VM) -> Result<()> {
+    let depth = vm.value_stack.len() as i64;
+    vm.value_stack.push(depth);
+    Ok(())
+}
+
+/// the forth `-rot` command
+///
+/// - move the top value below the next two, the opposite of `rot`
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::minus_rot;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(1);
+/// vm.value_stack.push(2);
+/// vm.value_stack.push(3);
+///
+/// minus_rot(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(2)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(3)));
+/// ```
+pub fn minus_rot(vm: &mut VM) -> Result<()> {
+    require(vm, 3)?;
+    let value = vm.value_stack.pop()?;
+    vm.value_stack.insert(2, value)
+}
+
+/// the forth `2drop` command
+///
+/// https://forth-standard.org/standard/core/TwoDROP
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::two_drop;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(1);
+/// vm.value_stack.push(2);
+/// vm.value_stack.push(3);
+///
+/// two_drop(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(two_drop(&mut vm), Err(Error::Stack));
+/// ```
+pub fn two_drop(vm: &mut VM) -> Result<()> {
+    require(vm, 2)?;
+    vm.value_stack.pop()?;
+    vm.value_stack.pop()?;
+    Ok(())
+}
+
+/// the forth `2dup` command
+///
+/// https://forth-standard.org/standard/core/TwoDUP
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::two_dup;
+/// let mut vm = VM::new_null();
+/// vm.value_stack.push(1);
+/// vm.value_stack.push(2);
+///
+/// two_dup(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(2)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(vm.value_stack.len(), 2);
+/// ```
+pub fn two_dup(vm: &mut VM) -> Result<()> {
+    let a = vm.value_stack.at(1)?.clone();
+    let b = vm.value_stack.at(0)?.clone();
+    vm.value_stack.push(a);
+    vm.value_stack.push(b);
+    Ok(())
+}
+
+/// the forth `2swap` command
+///
+/// https://forth-standard.org/standard/core/TwoSWAP
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::two_swap;
+/// let mut vm = VM::new_null();
+/// for value in 1..=4 {
+///     vm.value_stack.push(value);
+/// }
+///
+/// two_swap(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(2)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(4)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(3)));
+/// ```
+pub fn two_swap(vm: &mut VM) -> Result<()> {
+    require(vm, 4)?;
+    let a = vm.value_stack.remove(3)?;
+    let b = vm.value_stack.remove(2)?;
+    vm.value_stack.push(a);
+    vm.value_stack.push(b);
+    Ok(())
+}
+
+/// the forth `2over` command
+///
+/// https://forth-standard.org/standard/core/TwoOVER
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::two_over;
+/// let mut vm = VM::new_null();
+/// for value in 1..=4 {
+///     vm.value_stack.push(value);
+/// }
+///
+/// two_over(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(2)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(vm.value_stack.len(), 4);
+/// ```
+pub fn two_over(vm: &mut VM) -> Result<()> {
+    let a = vm.value_stack.at(3)?.clone();
+    let b = vm.value_stack.at(2)?.clone();
+    vm.value_stack.push(a);
+    vm.value_stack.push(b);
+    Ok(())
+}
+
+/// the forth `2rot` command
+///
+/// https://forth-standard.org/standard/double/TwoROT
+///
+/// - move the third pair to the top
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::two_rot;
+/// let mut vm = VM::new_null();
+/// for value in 1..=6 {
+///     vm.value_stack.push(value);
+/// }
+///
+/// two_rot(&mut vm);
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(2)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(1)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(6)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(5)));
+/// ```
+pub fn two_rot(vm: &mut VM) -> Result<()> {
+    require(vm, 6)?;
+    let a = vm.value_stack.remove(5)?;
+    let b = vm.value_stack.remove(4)?;
+    vm.value_stack.push(a);
+    vm.value_stack.push(b);
+    Ok(())
+}
+
 /// forth `drop` command
 ///
 /// removes last element from stack
@@ -455,6 +734,7 @@ pub fn drop(vm: &mut VM) -> Result<()> {
 
 /// forth line comment
 /// - drops everything till end of line
+///
 /// as we only process buffers line by line
 /// its save to just clear the complete buffer here
 pub fn lcomment(vm: &mut VM) -> Result<()> {
@@ -510,11 +790,8 @@ pub fn icomment(vm: &mut VM) -> Result<()> {
 /// ```
 ///
 pub fn negate(vm: &mut VM) -> Result<()> {
-    if let Ok(v) = vm.value_stack.at_mut(0) {
-        match v {
-            Variable::Int(v) => *v = *v * -1,
-            _ => {}
-        }
+    if let Ok(Variable::Int(v)) = vm.value_stack.at_mut(0) {
+        *v = v.wrapping_neg();
     }
     Ok(())
 }
@@ -582,11 +859,8 @@ pub fn eq(vm: &mut VM) -> Result<()> {
 ///
 /// ```
 pub fn abs(vm: &mut VM) -> Result<()> {
-    if let Ok(v) = vm.value_stack.at_mut(0) {
-        match v {
-            Variable::Int(v) => *v = v.abs(),
-            _ => {}
-        }
+    if let Ok(Variable::Int(v)) = vm.value_stack.at_mut(0) {
+        *v = v.wrapping_abs();
     }
     Ok(())
 }
@@ -595,7 +869,7 @@ pub fn abs(vm: &mut VM) -> Result<()> {
 ///
 /// prints `\n` to write
 pub fn cr(vm: &mut VM) -> Result<()> {
-    (vm.write)(&format!("\n"));
+    vm.write("\n")?;
     Ok(())
 }
 
@@ -605,7 +879,7 @@ pub fn cr(vm: &mut VM) -> Result<()> {
 ///
 /// prints ` ` to write
 pub fn space(vm: &mut VM) -> Result<()> {
-    (vm.write)(&format!(" "));
+    vm.write(" ")?;
     Ok(())
 }
 
@@ -615,11 +889,8 @@ pub fn space(vm: &mut VM) -> Result<()> {
 ///
 /// prints ` ` to write
 pub fn one_minus(vm: &mut VM) -> Result<()> {
-    if let Ok(v) = vm.value_stack.at_mut(0) {
-        match v {
-            Variable::Int(v) => *v = *v - 1,
-            _ => {}
-        }
+    if let Ok(Variable::Int(v)) = vm.value_stack.at_mut(0) {
+        *v = v.wrapping_sub(1);
     }
     Ok(())
 }
@@ -659,3 +930,163 @@ pub fn j(vm: &mut VM) -> Result<()> {
     vm.value_stack.push(idx);
     Ok(())
 }
+
+/// forth `emit` command
+///
+/// https://forth-standard.org/standard/core/EMIT
+///
+/// - pops a character code from the value stack
+/// - prints the character to the user
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::emit;
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+///
+/// vm.value_stack.push(65);
+///
+/// assert_eq!(emit(&mut vm), Ok(()));
+/// vm.flush();
+/// assert_eq!(output.take(), "A");
+///
+/// vm.value_stack.push("A");
+/// assert_eq!(emit(&mut vm), Err(Error::Type));
+/// ```
+pub fn emit(vm: &mut VM) -> Result<()> {
+    let code = match vm.value_stack.pop()? {
+        Variable::Int(code) => code,
+        _ => return Err(Error::Type),
+    };
+    let character = u32::try_from(code)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or(Error::Type)?;
+    vm.write(&character.to_string())?;
+    Ok(())
+}
+
+/// forth `key` command
+///
+/// https://forth-standard.org/standard/core/KEY
+///
+/// - reads a single character from the user
+/// - pushes its character code to the value stack
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::key;
+/// let mut vm = VM::new("y\n".as_bytes(), std::io::sink());
+///
+/// assert_eq!(key(&mut vm), Ok(()));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int('y' as i64)));
+///
+/// assert_eq!(key(&mut vm), Ok(()));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int('\n' as i64)));
+/// ```
+pub fn key(vm: &mut VM) -> Result<()> {
+    let key = vm.read_key()?.ok_or(Error::Io("end of input".to_owned()))?;
+    vm.value_stack.push(key as i64);
+    Ok(())
+}
+
+/// forth `accept` command
+///
+/// https://forth-standard.org/standard/core/ACCEPT
+///
+/// we have no memory to read into, so the string itself is pushed
+/// in place of the standard `c-addr`
+///
+/// - pops the maximum length `+n1` from the value stack
+/// - reads a line from the user
+/// - pushes the line (truncated to `+n1` characters)
+/// - pushes the number of characters `+n2`
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::accept;
+/// let mut vm = VM::new("hello world\n".as_bytes(), std::io::sink());
+///
+/// vm.value_stack.push(5);
+/// assert_eq!(accept(&mut vm), Ok(()));
+///
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(5)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::from("hello")));
+/// ```
+pub fn accept(vm: &mut VM) -> Result<()> {
+    let max: i64 = vm.value_stack.pop()?.into();
+    vm.reserve_string(max.max(0) as usize)?;
+    let line: String = vm
+        .read_line()?
+        .unwrap_or_default()
+        .chars()
+        .take(max.max(0) as usize)
+        .collect();
+    let len = line.chars().count() as i64;
+    vm.value_stack.push(line.as_str());
+    vm.value_stack.push(len);
+    Ok(())
+}
+
+/// forth `char` command
+///
+/// https://forth-standard.org/standard/core/CHAR
+///
+/// - takes the next token from the input buffer
+/// - pushes the character code of its first character
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::character;
+/// let mut vm = VM::new_null();
+/// vm.input_buffer = vec!["xyz".to_owned()].into();
+///
+/// assert_eq!(character(&mut vm), Ok(()));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int('x' as i64)));
+///
+/// assert_eq!(character(&mut vm), Err(Error::Parser("EOL".to_owned())));
+/// ```
+pub fn character(vm: &mut VM) -> Result<()> {
+    let token = vm
+        .input_buffer
+        .pop_front()
+        .ok_or(Error::Parser("EOL".to_owned()))?;
+    let first = token.chars().next().ok_or(Error::Parser(token.clone()))?;
+    vm.value_stack.push(first as i64);
+    Ok(())
+}
+
+/// forth `type` command
+///
+/// https://forth-standard.org/standard/core/TYPE
+///
+/// we have no memory to read from, so a string is expected
+/// in place of the standard `c-addr`
+///
+/// - pops the number of characters `u`
+/// - pops the string
+/// - prints the first `u` characters of the string
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::type_string;
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+///
+/// vm.value_stack.push("hello world");
+/// vm.value_stack.push(5);
+///
+/// assert_eq!(type_string(&mut vm), Ok(()));
+/// vm.flush();
+/// assert_eq!(output.take(), "hello");
+/// ```
+pub fn type_string(vm: &mut VM) -> Result<()> {
+    let len: i64 = vm.value_stack.pop()?.into();
+    let text = match vm.value_stack.pop()? {
+        Variable::String(text) => text,
+        _ => return Err(Error::Type),
+    };
+    let text: String = text.chars().take(len.max(0) as usize).collect();
+    vm.write(&text)?;
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+
+use crate::{
+    builtins::{runtime_do, superinstruction_name},
+    Cell, Dictionary, Error, Result, Stack, Variable, VM,
+};
+
+/// how far to run before the debugger pauses again
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+    /// only at breakpoints
+    #[default]
+    Run,
+    /// at the next cell, also inside called words
+    Step,
+    /// at the next cell with at most this many frames
+    Over(usize),
+}
+
+/// state of the step debugger
+///
+/// the inner interpreter asks `pause` before every cell while it is `active`
+#[derive(Debug, Default)]
+pub struct Debugger {
+    /// lowercase names of the words to stop at
+    pub breakpoints: HashSet<String>,
+    pub mode: Mode,
+}
+
+impl Debugger {
+    pub fn active(&self) -> bool {
+        self.mode != Mode::Run || !self.breakpoints.is_empty()
+    }
+}
+
+/// commands understood at a pause
+pub const DEBUG_HELP: &str = "\
+s step    run the next cell, go into calls
+n next    run the next cell, go over calls
+o out     run until the word returns
+c cont    run until the next breakpoint
+q quit    remove all breakpoints and run";
+
+/// readable form of a cell
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::cell_name;
+/// let mut dict = Dictionary::new();
+/// builtins::add_core(&mut dict);
+///
+/// assert_eq!(cell_name(&dict, &Cell::Exec(builtins::dup)), "dup");
+/// assert_eq!(cell_name(&dict, &Cell::Data(Variable::Int(3))), "3");
+/// assert_eq!(cell_name(&dict, &Cell::Exec(builtins::runtime_one_plus)), "1 +");
+/// assert_eq!(cell_name(&dict, &Cell::ControlBranchIfZero(3)), "0branch>3");
+/// ```
+pub fn cell_name(dictionary: &Dictionary, cell: &Cell) -> String {
+    if let Some(name) = match cell {
+        Cell::Exec(word) => superinstruction_name(*word),
+        _ => None,
+    } {
+        return name.to_owned();
+    }
+    match cell {
+        // compiled by `DO`, not a word of its own
+        Cell::Exec(word)
+            if std::ptr::fn_addr_eq(*word, runtime_do as fn(&mut VM) -> Result<()>) =>
+        {
+            "do".to_owned()
+        }
+        Cell::Exec(_) | Cell::Compiled(_) | Cell::Compiler(_) | Cell::Closure(_) => {
+            dictionary.name_of(cell).unwrap_or("<native>").to_owned()
+        }
+        Cell::Data(Variable::String(text)) => format!("s\" {}\"", text),
+        Cell::Data(value) => value.to_string(),
+        Cell::Call(name, _) | Cell::Inlined(name, ..) => name.to_string(),
+        Cell::Recurse => "recurse".to_owned(),
+        Cell::Print(text) => format!(".\" {}\"", text),
+        Cell::ControlReturn => "exit".to_owned(),
+        Cell::ControlBranch(step) => format!("branch>{}", step),
+        Cell::ControlBranchIfZero(step) => format!("0branch>{}", step),
+        Cell::ControlBranchIfNotZero(step) => format!("?branch>{}", step),
+        Cell::ControlLoop(step) => format!("loop>{}", step),
+        Cell::ControlPlusLoop(step) => format!("+loop>{}", step),
+        Cell::ControlMinusLoop(step) => format!("-loop>{}", step),
+    }
+}
+
+fn stack_line(name: &str, stack: &Stack) -> String {
+    let mut line = format!("  {} <{}>", name, stack.len());
+    // the top is shown last, like `.s` in most forths
+    let values: Vec<String> = stack.iter().map(|value| value.to_string()).collect();
+    for value in values.iter().rev() {
+        line.push(' ');
+        line.push_str(value);
+    }
+    line.push('\n');
+    line
+}
+
+/// where the innermost word is, the next cell in brackets
+fn location(vm: &VM, pc: usize) -> Result<String> {
+    let frame = vm.frames.last().ok_or(Error::Executor)?;
+    let name = match &*frame.name {
+        "" => "<anonymous>",
+        name => name,
+    };
+    let mut line = format!("{}:", name);
+    // the copies of inlined definitions are left out
+    let mut next = 0;
+    for (index, cell) in frame.code.iter().enumerate() {
+        if index < next {
+            continue;
+        }
+        next = match cell {
+            Cell::Inlined(_, _, step) => index.saturating_add(*step as usize),
+            _ => index + 1,
+        };
+        let cell = cell_name(&vm.dictionary, cell);
+        match index == pc {
+            true => line.push_str(&format!(" [{}]", cell)),
+            false => line.push_str(&format!(" {}", cell)),
+        }
+    }
+    line.push('\n');
+    line.push_str(&stack_line("stack", &vm.value_stack));
+    if !vm.return_stack.is_empty() {
+        line.push_str(&stack_line("return", &vm.return_stack));
+    }
+    Ok(line)
+}
+
+/// called by the inner interpreter before the cell at `pc` runs
+///
+/// shows where it is and reads commands from the input of the VM
+/// until one of them runs on. at the end of the input the program runs on as well.
+pub fn pause(vm: &mut VM, pc: usize) -> Result<()> {
+    let depth = vm.frames.len();
+    let stop = match vm.debugger.mode {
+        Mode::Step => true,
+        Mode::Over(frames) => depth <= frames,
+        Mode::Run => {
+            pc == 0
+                && vm.frames.last().is_some_and(|frame| {
+                    vm.debugger.breakpoints.contains(&frame.name.to_lowercase())
+                })
+        }
+    };
+    if !stop {
+        return Ok(());
+    }
+
+    let location = location(vm, pc)?;
+    vm.write(&location)?;
+    loop {
+        vm.write("debug> ")?;
+        vm.flush()?;
+        let Some(line) = vm.read_line()? else {
+            vm.debugger.mode = Mode::Run;
+            return Ok(());
+        };
+        vm.debugger.mode = match line.trim().to_lowercase().as_str() {
+            "" | "s" | "step" => Mode::Step,
+            "n" | "next" => Mode::Over(depth),
+            "o" | "out" => Mode::Over(depth - 1),
+            "c" | "cont" => Mode::Run,
+            "q" | "quit" => {
+                vm.debugger.breakpoints.clear();
+                Mode::Run
+            }
+            _ => {
+                vm.write(DEBUG_HELP)?;
+                vm.write("\n")?;
+                continue;
+            }
+        };
+        return Ok(());
+    }
+}
+
+/// forth `debug` command
+///
+/// - takes the next token as the name of a word
+/// - pauses every time that word starts, see `pause`
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new("n\nn\n".as_bytes(), output.clone());
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_tools(&mut vm.dictionary);
+///
+/// vm.eval(": sq dup * ;");
+/// vm.eval("debug sq 3 sq .");
+///
+/// assert_eq!(output.take(), "\
+/// sq: [dup] *
+///   stack <1> 3
+/// debug> sq: dup [*]
+///   stack <2> 3 3
+/// debug> 9 ");
+/// ```
+pub fn debug(vm: &mut VM) -> Result<()> {
+    let name = vm
+        .input_buffer
+        .pop_front()
+        .ok_or(Error::Parser("EOL".to_owned()))?;
+    vm.lookup(&name)?;
+    vm.debugger.breakpoints.insert(name.to_lowercase());
+    Ok(())
+}
+
+/// forth `undebug` command
+///
+/// - takes the next token as the name of a word
+/// - removes its breakpoint
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new("c\n".as_bytes(), output.clone());
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_tools(&mut vm.dictionary);
+///
+/// vm.eval(": sq dup * ; debug sq undebug sq 3 sq .");
+///
+/// assert_eq!(output.take(), "9 ");
+/// assert!(!vm.debugger.active());
+/// ```
+pub fn undebug(vm: &mut VM) -> Result<()> {
+    let name = vm
+        .input_buffer
+        .pop_front()
+        .ok_or(Error::Parser("EOL".to_owned()))?;
+    vm.debugger.breakpoints.remove(&name.to_lowercase());
+    Ok(())
+}
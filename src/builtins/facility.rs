@@ -0,0 +1,33 @@
+use crate::Result;
+use crate::VM;
+
+/// forth `key?` command
+///
+/// https://forth-standard.org/standard/facility/KEYq
+///
+/// - pushes `true` (-1) if a character is pending for `key`
+/// - else 0
+///
+/// this never blocks, so only characters that are already
+/// buffered from the last `read` are taken into account.
+///
+/// ```
+/// # use frust::*;
+/// # use frust::builtins::{key, key_q};
+/// let mut vm = VM::new("y\n".as_bytes(), std::io::sink());
+///
+/// key_q(&mut vm);
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+///
+/// key(&mut vm);
+/// key_q(&mut vm);
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(-1)));
+/// ```
+pub fn key_q(vm: &mut VM) -> Result<()> {
+    if vm.key_buffer.is_empty() {
+        vm.value_stack.push(0);
+    } else {
+        vm.value_stack.push(-1);
+    }
+    Ok(())
+}
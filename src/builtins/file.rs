@@ -0,0 +1,590 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result, Variable, VM};
+
+/// file access method `r/o`
+pub const READ_ONLY: i64 = 0;
+/// file access method `w/o`
+pub const WRITE_ONLY: i64 = 1;
+/// file access method `r/w`
+pub const READ_WRITE: i64 = 2;
+
+/// `ior` of a failed file operation without an os error code
+///
+/// this is the standard `THROW` code for "file I/O exception"
+const IOR_FAILED: i64 = -37;
+
+/// state of the file access word set
+///
+/// - open files by their `fileid`
+/// - where to look for included files
+/// - which files were included so far
+#[derive(Debug, Default)]
+pub struct Files {
+    handles: HashMap<i64, BufReader<File>>,
+    next_id: i64,
+    /// directories searched for relative paths after the
+    /// directory of the including file and the working directory
+    pub search_path: Vec<PathBuf>,
+    /// files that are currently being included, innermost last
+    pub including: Vec<PathBuf>,
+    /// every file that was included so far
+    pub included: HashSet<PathBuf>,
+}
+
+impl Files {
+    /// find a file to include
+    ///
+    /// relative paths are tried
+    /// - relative to the file that is currently included
+    /// - relative to the working directory
+    /// - relative to each directory of the `search_path`
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let path = Path::new(name);
+        let mut candidates = Vec::new();
+        if path.is_absolute() {
+            candidates.push(path.to_path_buf());
+        } else {
+            if let Some(dir) = self.including.last().and_then(|file| file.parent()) {
+                candidates.push(dir.join(path));
+            }
+            candidates.push(path.to_path_buf());
+            candidates.extend(self.search_path.iter().map(|dir| dir.join(path)));
+        }
+        candidates
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+            .map(|found| found.canonicalize().unwrap_or(found))
+    }
+
+    fn insert(&mut self, file: File) -> i64 {
+        self.next_id += 1;
+        self.handles.insert(self.next_id, BufReader::new(file));
+        self.next_id
+    }
+
+    fn get(&mut self, id: i64) -> std::io::Result<&mut BufReader<File>> {
+        self.handles
+            .get_mut(&id)
+            .ok_or(std::io::ErrorKind::NotFound.into())
+    }
+}
+
+/// turn the result of a file operation into an `ior`
+fn ior<T>(result: &std::io::Result<T>) -> i64 {
+    match result {
+        Ok(_) => 0,
+        Err(error) => error.raw_os_error().map(i64::from).unwrap_or(IOR_FAILED),
+    }
+}
+
+/// pop a `str u` pair
+///
+/// we have no memory, the string is passed in place of the standard `c-addr`
+fn pop_string(vm: &mut VM) -> Result<String> {
+    let len: i64 = vm.value_stack.pop()?.into();
+    match vm.value_stack.pop()? {
+        Variable::String(text) => Ok(text.chars().take(len.max(0) as usize).collect()),
+        _ => Err(Error::Type),
+    }
+}
+
+fn pop_int(vm: &mut VM) -> Result<i64> {
+    match vm.value_stack.pop()? {
+        Variable::Int(value) => Ok(value),
+        _ => Err(Error::Type),
+    }
+}
+
+fn open(name: &str, fam: i64, create: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    match fam {
+        READ_ONLY => options.read(true),
+        WRITE_ONLY => options.write(true),
+        _ => options.read(true).write(true),
+    };
+    if create {
+        options.write(true).create(true).truncate(true);
+    }
+    options.open(name)
+}
+
+/// forth `r/o` command
+///
+/// https://forth-standard.org/standard/file/RDivO
+pub fn read_only(vm: &mut VM) -> Result<()> {
+    vm.value_stack.push(READ_ONLY);
+    Ok(())
+}
+
+/// forth `w/o` command
+///
+/// https://forth-standard.org/standard/file/WDivO
+pub fn write_only(vm: &mut VM) -> Result<()> {
+    vm.value_stack.push(WRITE_ONLY);
+    Ok(())
+}
+
+/// forth `r/w` command
+///
+/// https://forth-standard.org/standard/file/RDivW
+pub fn read_write(vm: &mut VM) -> Result<()> {
+    vm.value_stack.push(READ_WRITE);
+    Ok(())
+}
+
+/// forth `bin` command
+///
+/// https://forth-standard.org/standard/file/BIN
+///
+/// all files are binary files, the access method is left unchanged
+pub fn bin(vm: &mut VM) -> Result<()> {
+    let fam = pop_int(vm)?;
+    vm.value_stack.push(fam);
+    Ok(())
+}
+
+/// forth `open-file` command
+///
+/// https://forth-standard.org/standard/file/OPEN-FILE
+///
+/// ( str u fam -- fileid ior )
+///
+/// ```
+/// # use frust::*;
+/// # let path = std::env::temp_dir().join("frust-doc-open-file.fr");
+/// # std::fs::write(&path, "1 2 +\n").unwrap();
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_file(&mut vm.dictionary);
+///
+/// vm.eval(&format!("s\" {}\" r/o open-file", path.display()));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+///
+/// vm.eval("s\" /does/not/exist\" r/o open-file");
+/// assert_ne!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn open_file(vm: &mut VM) -> Result<()> {
+    let fam = pop_int(vm)?;
+    let name = pop_string(vm)?;
+    match open(&name, fam, false) {
+        Ok(file) => {
+            let id = vm.files.insert(file);
+            vm.value_stack.push(id);
+            vm.value_stack.push(0);
+        }
+        Err(error) => {
+            vm.value_stack.push(0);
+            vm.value_stack.push(ior::<()>(&Err(error)));
+        }
+    }
+    Ok(())
+}
+
+/// forth `create-file` command
+///
+/// https://forth-standard.org/standard/file/CREATE-FILE
+///
+/// ( str u fam -- fileid ior )
+///
+/// an existing file is truncated
+pub fn create_file(vm: &mut VM) -> Result<()> {
+    let fam = pop_int(vm)?;
+    let name = pop_string(vm)?;
+    match open(&name, fam, true) {
+        Ok(file) => {
+            let id = vm.files.insert(file);
+            vm.value_stack.push(id);
+            vm.value_stack.push(0);
+        }
+        Err(error) => {
+            vm.value_stack.push(0);
+            vm.value_stack.push(ior::<()>(&Err(error)));
+        }
+    }
+    Ok(())
+}
+
+/// forth `close-file` command
+///
+/// https://forth-standard.org/standard/file/CLOSE-FILE
+///
+/// ( fileid -- ior )
+pub fn close_file(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let result = vm
+        .files
+        .handles
+        .remove(&id)
+        .map(|mut file| file.get_mut().flush())
+        .unwrap_or(Err(std::io::ErrorKind::NotFound.into()));
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `read-line` command
+///
+/// https://forth-standard.org/standard/file/READ-LINE
+///
+/// ( u1 fileid -- str u2 flag ior )
+///
+/// we have no memory to read into, so the line is pushed
+/// in place of the standard `c-addr`. `flag` is false at the end of the file.
+///
+/// ```
+/// # use frust::*;
+/// # let path = std::env::temp_dir().join("frust-doc-read-line.txt");
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_file(&mut vm.dictionary);
+///
+/// vm.eval(&format!("s\" {}\" w/o create-file drop", path.display()));
+/// vm.eval("dup s\" hello file\" rot write-line drop close-file drop");
+///
+/// vm.eval(&format!("s\" {}\" r/o open-file drop", path.display()));
+/// vm.eval("dup 80 swap read-line");
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(-1)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(10)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::from("hello file")));
+///
+/// vm.eval("dup 80 swap read-line");
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+/// # vm.eval("drop drop close-file drop");
+/// # vm.eval(&format!("s\" {}\" delete-file", path.display()));
+/// # assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+/// ```
+pub fn read_line(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let max: i64 = vm.value_stack.pop()?.into();
+    vm.reserve_string(max.max(0) as usize)?;
+    let mut line = String::new();
+    let result = vm.files.get(id).and_then(|file| file.read_line(&mut line));
+    let more = matches!(result, Ok(len) if len > 0);
+    let line: String = line
+        .trim_end_matches(['\n', '\r'])
+        .chars()
+        .take(max.max(0) as usize)
+        .collect();
+    let len = line.chars().count() as i64;
+    vm.value_stack.push(line.as_str());
+    vm.value_stack.push(len);
+    vm.value_stack.push(if more { -1 } else { 0 });
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `read-file` command
+///
+/// https://forth-standard.org/standard/file/READ-FILE
+///
+/// ( u1 fileid -- str u2 ior )
+///
+/// we have no memory to read into, so the text is pushed
+/// in place of the standard `c-addr`. `u2` is 0 at the end of the file.
+pub fn read_file(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let max: i64 = vm.value_stack.pop()?.into();
+    vm.reserve_string(max.max(0) as usize)?;
+    let mut buffer = Vec::new();
+    let result = vm.files.get(id).and_then(|file| {
+        file.by_ref()
+            .take(max.max(0) as u64)
+            .read_to_end(&mut buffer)
+    });
+    let text = String::from_utf8_lossy(&buffer);
+    vm.value_stack.push(text.as_ref());
+    vm.value_stack.push(text.chars().count() as i64);
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+fn write(vm: &mut VM, newline: bool) -> Result<()> {
+    let id = pop_int(vm)?;
+    let mut text = pop_string(vm)?;
+    if newline {
+        text.push('\n');
+    }
+    let result = vm.files.get(id).and_then(|file| {
+        // drop what was read ahead, so we write at the logical position
+        let position = file.stream_position()?;
+        file.seek(SeekFrom::Start(position))?;
+        file.get_mut().write_all(text.as_bytes())
+    });
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `write-file` command
+///
+/// https://forth-standard.org/standard/file/WRITE-FILE
+///
+/// ( str u fileid -- ior )
+pub fn write_file(vm: &mut VM) -> Result<()> {
+    write(vm, false)
+}
+
+/// forth `write-line` command
+///
+/// https://forth-standard.org/standard/file/WRITE-LINE
+///
+/// ( str u fileid -- ior )
+pub fn write_line(vm: &mut VM) -> Result<()> {
+    write(vm, true)
+}
+
+/// forth `flush-file` command
+///
+/// https://forth-standard.org/standard/file/FLUSH-FILE
+///
+/// ( fileid -- ior )
+pub fn flush_file(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let result = vm.files.get(id).and_then(|file| file.get_mut().flush());
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `file-size` command
+///
+/// https://forth-standard.org/standard/file/FILE-SIZE
+///
+/// ( fileid -- u ior )
+///
+/// we have no double cells, the size is a single cell
+///
+/// ```
+/// # use frust::*;
+/// # let path = std::env::temp_dir().join("frust-doc-file-size.txt");
+/// # std::fs::write(&path, "12345").unwrap();
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_file(&mut vm.dictionary);
+///
+/// vm.eval(&format!("s\" {}\" r/o open-file drop file-size", path.display()));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(5)));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn file_size(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let result = vm.files.get(id).and_then(|file| file.get_ref().metadata());
+    vm.value_stack
+        .push(result.as_ref().map(|meta| meta.len() as i64).unwrap_or(0));
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `file-position` command
+///
+/// https://forth-standard.org/standard/file/FILE-POSITION
+///
+/// ( fileid -- u ior )
+pub fn file_position(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let result = vm.files.get(id).and_then(|file| file.stream_position());
+    vm.value_stack.push(*result.as_ref().unwrap_or(&0) as i64);
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `reposition-file` command
+///
+/// https://forth-standard.org/standard/file/REPOSITION-FILE
+///
+/// ( u fileid -- ior )
+pub fn reposition_file(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let position = pop_int(vm)?;
+    let result = vm
+        .files
+        .get(id)
+        .and_then(|file| file.seek(SeekFrom::Start(position.max(0) as u64)));
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `delete-file` command
+///
+/// https://forth-standard.org/standard/file/DELETE-FILE
+///
+/// ( str u -- ior )
+pub fn delete_file(vm: &mut VM) -> Result<()> {
+    let name = pop_string(vm)?;
+    let result = std::fs::remove_file(name);
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `rename-file` command
+///
+/// https://forth-standard.org/standard/file/RENAME-FILE
+///
+/// ( str1 u1 str2 u2 -- ior )
+pub fn rename_file(vm: &mut VM) -> Result<()> {
+    let to = pop_string(vm)?;
+    let from = pop_string(vm)?;
+    let result = std::fs::rename(from, to);
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `file-status` command
+///
+/// https://forth-standard.org/standard/file/FILE-STATUS
+///
+/// ( str u -- x ior )
+///
+/// `x` is the size of the file
+pub fn file_status(vm: &mut VM) -> Result<()> {
+    let name = pop_string(vm)?;
+    let result = std::fs::metadata(name);
+    vm.value_stack
+        .push(result.as_ref().map(|meta| meta.len() as i64).unwrap_or(0));
+    vm.value_stack.push(ior(&result));
+    Ok(())
+}
+
+/// forth `include-file` command
+///
+/// https://forth-standard.org/standard/file/INCLUDE-FILE
+///
+/// ( fileid -- )
+///
+/// interprets the rest of an open file, the file is closed afterwards
+pub fn include_file(vm: &mut VM) -> Result<()> {
+    let id = pop_int(vm)?;
+    let mut file = vm
+        .files
+        .handles
+        .remove(&id)
+        .ok_or(Error::Io(format!("invalid fileid {}", id)))?;
+    let mut source = String::new();
+    file.read_to_string(&mut source)
+        .map_err(|error| Error::Io(error.to_string()))?;
+    vm.include_source(&format!("fileid {}", id), &source)
+}
+
+/// forth `included` command
+///
+/// https://forth-standard.org/standard/file/INCLUDED
+///
+/// ( str u -- )
+///
+/// ```
+/// # use frust::*;
+/// # let dir = std::env::temp_dir().join("frust-doc-included");
+/// # std::fs::create_dir_all(dir.join("lib")).unwrap();
+/// # std::fs::write(dir.join("main.fr"), "include lib/double.fr\n: quadruple double double ;\n").unwrap();
+/// # std::fs::write(dir.join("lib/double.fr"), ": double\n  2 * ;\n").unwrap();
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_file(&mut vm.dictionary);
+///
+/// // lib/double.fr is found relative to main.fr
+/// vm.eval(&format!("s\" {}\" included", dir.join("main.fr").display()));
+/// vm.eval("3 quadruple");
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(12)));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn included(vm: &mut VM) -> Result<()> {
+    let name = pop_string(vm)?;
+    vm.include(&name)
+}
+
+/// forth `include` command
+///
+/// https://forth-standard.org/standard/file/INCLUDE
+///
+/// ( "name" -- )
+pub fn include(vm: &mut VM) -> Result<()> {
+    let name = vm
+        .input_buffer
+        .pop_front()
+        .ok_or(Error::Parser("EOL".to_owned()))?;
+    vm.include(&name)
+}
+
+/// forth `required` command
+///
+/// https://forth-standard.org/standard/file/REQUIRED
+///
+/// ( str u -- )
+///
+/// like `included`, but files that were included before are skipped
+///
+/// ```
+/// # use frust::*;
+/// # let path = std::env::temp_dir().join("frust-doc-required.fr");
+/// # std::fs::write(&path, "1\n").unwrap();
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_file(&mut vm.dictionary);
+///
+/// vm.eval(&format!("s\" {0}\" required s\" {0}\" required", path.display()));
+/// assert_eq!(vm.value_stack.len(), 1);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn required(vm: &mut VM) -> Result<()> {
+    let name = pop_string(vm)?;
+    vm.require(&name)
+}
+
+/// forth `require` command
+///
+/// https://forth-standard.org/standard/file/REQUIRE
+///
+/// ( "name" -- )
+pub fn require(vm: &mut VM) -> Result<()> {
+    let name = vm
+        .input_buffer
+        .pop_front()
+        .ok_or(Error::Parser("EOL".to_owned()))?;
+    vm.require(&name)
+}
+
+/// forth `save-image` command
+///
+/// ( str u -- )
+///
+/// writes an image of the dictionary, the stacks and the settings
+/// to the file, see `image`
+///
+/// ```
+/// # use frust::*;
+/// # let path = std::env::temp_dir().join("frust-doc-save-image.img");
+/// let mut vm = VM::new_null();
+/// builtins::add_all(&mut vm.dictionary);
+/// vm.eval(": square dup * ;");
+/// vm.eval(&format!("s\" {}\" save-image", path.display()));
+///
+/// let mut other = VM::new_null();
+/// builtins::add_all(&mut other.dictionary);
+/// other.eval(&format!("s\" {}\" load-image 7 square", path.display()));
+/// assert_eq!(other.value_stack.pop(), Ok(Variable::Int(49)));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn save_image(vm: &mut VM) -> Result<()> {
+    let name = pop_string(vm)?;
+    let mut file = File::create(name).map_err(|error| Error::Io(error.to_string()))?;
+    crate::save_image(vm, &mut file)
+}
+
+/// forth `load-image` command
+///
+/// ( str u -- )
+///
+/// replaces the dictionary, the stacks and the settings
+/// with an image written by `save-image`
+pub fn load_image(vm: &mut VM) -> Result<()> {
+    let name = pop_string(vm)?;
+    let mut file = File::open(name).map_err(|error| Error::Io(error.to_string()))?;
+    crate::load_image(vm, &mut file)
+}
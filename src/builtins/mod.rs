@@ -1,7 +1,176 @@
 mod compiled;
 mod core;
+mod debugger;
+mod facility;
+mod file;
+mod profiler;
+mod tester;
 mod tools;
 
 pub use compiled::*;
 pub use core::*;
+pub use debugger::*;
+pub use facility::*;
+pub use file::*;
+pub use profiler::*;
+pub use tester::*;
 pub use tools::*;
+
+use crate::{Cell, Dictionary};
+
+/// add the words of the core word set
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+///
+/// vm.eval("1 2 + dup");
+/// assert_eq!(vm.value_stack.len(), 2);
+/// ```
+pub fn add_core(dictionary: &mut Dictionary) {
+    dictionary.add("+", Cell::Exec(plus));
+    dictionary.add("-", Cell::Exec(minus));
+    dictionary.add("*", Cell::Exec(times));
+    dictionary.add("/", Cell::Exec(div));
+    dictionary.add("mod", Cell::Exec(modulo));
+    dictionary.add("\\", Cell::Exec(lcomment));
+    dictionary.add("(", Cell::Compiler(compiletime_icomment));
+    dictionary.add(".", Cell::Exec(dot));
+    dictionary.add("cr", Cell::Exec(cr));
+    dictionary.add("space", Cell::Exec(space));
+    dictionary.add("1-", Cell::Exec(one_minus));
+    dictionary.add("dup", Cell::Exec(dup));
+    dictionary.add("abs", Cell::Exec(abs));
+    dictionary.add("=", Cell::Exec(eq));
+    dictionary.add("max", Cell::Exec(max));
+    dictionary.add("min", Cell::Exec(min));
+    dictionary.add("nip", Cell::Exec(nip));
+    dictionary.add("roll", Cell::Exec(roll));
+    dictionary.add("pick", Cell::Exec(pick));
+    dictionary.add("depth", Cell::Exec(depth));
+    dictionary.add("-rot", Cell::Exec(minus_rot));
+    dictionary.add("2drop", Cell::Exec(two_drop));
+    dictionary.add("2dup", Cell::Exec(two_dup));
+    dictionary.add("2swap", Cell::Exec(two_swap));
+    dictionary.add("2over", Cell::Exec(two_over));
+    dictionary.add("2rot", Cell::Exec(two_rot));
+    dictionary.add("over", Cell::Exec(over));
+    dictionary.add("tuck", Cell::Exec(tuck));
+    dictionary.add("negate", Cell::Exec(negate));
+    dictionary.add("swap", Cell::Exec(swap));
+    dictionary.add("rot", Cell::Exec(rot));
+    dictionary.add("drop", Cell::Exec(drop));
+    dictionary.add("?dup", Cell::Exec(qdup));
+    dictionary.add("i", Cell::Exec(i));
+    dictionary.add("j", Cell::Exec(j));
+    dictionary.add("emit", Cell::Exec(emit));
+    dictionary.add("key", Cell::Exec(key));
+    dictionary.add("accept", Cell::Exec(accept));
+    dictionary.add("char", Cell::Exec(character));
+    dictionary.add("type", Cell::Exec(type_string));
+    dictionary.add("if", Cell::Compiler(compiletime_if));
+    dictionary.add("do", Cell::Compiler(compiletime_do));
+    dictionary.add(".\"", Cell::Compiler(compiletime_dot_q));
+    dictionary.add("s\"", Cell::Compiler(compiletime_s_quote));
+    dictionary.add("[char]", Cell::Compiler(compiletime_bracket_char));
+    dictionary.add("recurse", Cell::Compiler(compiletime_recurse));
+}
+
+/// add the words of the facility word set
+pub fn add_facility(dictionary: &mut Dictionary) {
+    dictionary.add("key?", Cell::Exec(key_q));
+}
+
+/// add the words of the programming-tools word set
+pub fn add_tools(dictionary: &mut Dictionary) {
+    dictionary.add(".s", Cell::Exec(dot_s));
+    dictionary.add("bye", Cell::Exec(bye));
+    dictionary.add("debug", Cell::Exec(debug));
+    dictionary.add("undebug", Cell::Exec(undebug));
+    dictionary.add("profile", Cell::Exec(profile));
+    dictionary.add(".profile", Cell::Exec(dot_profile));
+}
+
+/// add the words of the file access word set
+pub fn add_file(dictionary: &mut Dictionary) {
+    dictionary.add("r/o", Cell::Exec(read_only));
+    dictionary.add("w/o", Cell::Exec(write_only));
+    dictionary.add("r/w", Cell::Exec(read_write));
+    dictionary.add("bin", Cell::Exec(bin));
+    dictionary.add("open-file", Cell::Exec(open_file));
+    dictionary.add("create-file", Cell::Exec(create_file));
+    dictionary.add("close-file", Cell::Exec(close_file));
+    dictionary.add("read-line", Cell::Exec(read_line));
+    dictionary.add("read-file", Cell::Exec(read_file));
+    dictionary.add("write-line", Cell::Exec(write_line));
+    dictionary.add("write-file", Cell::Exec(write_file));
+    dictionary.add("flush-file", Cell::Exec(flush_file));
+    dictionary.add("file-size", Cell::Exec(file_size));
+    dictionary.add("file-position", Cell::Exec(file_position));
+    dictionary.add("reposition-file", Cell::Exec(reposition_file));
+    dictionary.add("delete-file", Cell::Exec(delete_file));
+    dictionary.add("rename-file", Cell::Exec(rename_file));
+    dictionary.add("file-status", Cell::Exec(file_status));
+    dictionary.add("include-file", Cell::Exec(include_file));
+    dictionary.add("included", Cell::Exec(included));
+    dictionary.add("include", Cell::Exec(include));
+    dictionary.add("required", Cell::Exec(required));
+    dictionary.add("require", Cell::Exec(require));
+    dictionary.add("save-image", Cell::Exec(save_image));
+    dictionary.add("load-image", Cell::Exec(load_image));
+}
+
+/// add the Hayes tester words `T{ -> }T` and `TESTING`
+///
+/// not part of `add_all`, test runners load them on demand
+pub fn add_tester(dictionary: &mut Dictionary) {
+    dictionary.add("t{", Cell::Exec(t_open));
+    dictionary.add("->", Cell::Exec(t_arrow));
+    dictionary.add("}t", Cell::Exec(t_close));
+    dictionary.add("testing", Cell::Exec(testing));
+}
+
+/// a group of words that is added at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WordSet {
+    Core,
+    Facility,
+    Tools,
+    File,
+    Tester,
+}
+
+impl WordSet {
+    /// add the words of the set, like `add_core` and friends
+    pub fn add(self, dictionary: &mut Dictionary) {
+        match self {
+            WordSet::Core => add_core(dictionary),
+            WordSet::Facility => add_facility(dictionary),
+            WordSet::Tools => add_tools(dictionary),
+            WordSet::File => add_file(dictionary),
+            WordSet::Tester => add_tester(dictionary),
+        }
+    }
+
+    /// names of the words in the set
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let names = builtins::WordSet::Tools.names();
+    /// assert!(names.contains(&"bye".to_owned()));
+    /// ```
+    pub fn names(self) -> Vec<String> {
+        let mut dictionary = Dictionary::new();
+        self.add(&mut dictionary);
+        dictionary.names().map(|name| name.to_owned()).collect()
+    }
+}
+
+/// add all implemented word sets
+pub fn add_all(dictionary: &mut Dictionary) {
+    add_core(dictionary);
+    add_facility(dictionary);
+    add_tools(dictionary);
+    add_file(dictionary);
+}
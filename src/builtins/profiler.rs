@@ -0,0 +1,329 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    builtins::{runtime_do, SUPERINSTRUCTIONS},
+    Cell, Closure, Dictionary, Error, Result, VM,
+};
+
+/// a profiled word, natives are told apart by their function or `Closure::id`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Word(Arc<str>),
+    Native(usize),
+}
+
+/// totals of one word
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration,
+}
+
+/// a word that is running
+#[derive(Debug)]
+struct Active {
+    key: Key,
+    /// node of the call path in `Profiler::nodes`
+    node: usize,
+    /// number of frames when it started
+    depth: usize,
+    start: Instant,
+    /// time spent in words it called
+    children: Duration,
+}
+
+/// one call path, for the collapsed stacks
+#[derive(Debug)]
+struct Node {
+    parent: Option<usize>,
+    key: Key,
+    exclusive: Duration,
+}
+
+/// what the profiler found out about one word
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordProfile {
+    pub name: String,
+    pub calls: u64,
+    /// time from start to return, only the outermost of recursive calls counts
+    pub inclusive: Duration,
+    /// inclusive time without the words it called
+    pub exclusive: Duration,
+}
+
+/// counts calls and measures time of every word while `enabled`
+///
+/// words of a single native cell are measured as the native,
+/// so `dup` counts the same when it is interpreted or compiled.
+/// words that the optimizer inlined are called while profiling, like
+/// any other word.
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+///
+/// vm.eval(": sq dup * ; : f 0 do i sq drop loop ;");
+/// vm.profiler.enabled = true;
+/// vm.eval("3 f");
+/// vm.profiler.enabled = false;
+///
+/// let report = vm.profiler.report(&vm.dictionary);
+/// assert_eq!(report[0].name, "f");
+/// let calls = |name: &str| report.iter().find(|word| word.name == name).unwrap().calls;
+/// assert_eq!(calls("f"), 1);
+/// assert_eq!(calls("sq"), 3);
+/// assert_eq!(calls("dup"), 3);
+/// assert!(report[0].inclusive >= report[0].exclusive);
+///
+/// let collapsed = vm.profiler.collapsed(&vm.dictionary);
+/// assert!(collapsed.lines().any(|line| line.starts_with("f;sq;dup ")));
+/// ```
+#[derive(Debug, Default)]
+pub struct Profiler {
+    pub enabled: bool,
+    active: Vec<Active>,
+    totals: HashMap<Key, Totals>,
+    /// how often each word is running right now, for recursion
+    running: HashMap<Key, usize>,
+    nodes: Vec<Node>,
+    children: HashMap<(Option<usize>, Key), usize>,
+}
+
+impl Profiler {
+    /// forget everything measured so far
+    pub fn clear(&mut self) {
+        *self = Profiler {
+            enabled: self.enabled,
+            ..Profiler::default()
+        };
+    }
+
+    fn enter(&mut self, key: Key, depth: usize) {
+        let parent = self.active.last().map(|active| active.node);
+        let next = self.nodes.len();
+        let node = *self.children.entry((parent, key.clone())).or_insert(next);
+        if node == next {
+            self.nodes.push(Node {
+                parent,
+                key: key.clone(),
+                exclusive: Duration::ZERO,
+            });
+        }
+        self.totals.entry(key.clone()).or_default().calls += 1;
+        *self.running.entry(key.clone()).or_default() += 1;
+        self.active.push(Active {
+            key,
+            node,
+            depth,
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    /// a definition starts with `depth` frames
+    pub fn enter_word(&mut self, name: Arc<str>, depth: usize) {
+        self.enter(Key::Word(name), depth);
+    }
+
+    /// a native word starts inside a definition with `depth` frames
+    pub fn enter_native(&mut self, word: fn(&mut VM) -> Result<()>, depth: usize) {
+        self.enter(Key::Native(word as usize), depth);
+    }
+
+    /// a closure starts inside a definition with `depth` frames
+    pub fn enter_closure(&mut self, word: &Closure, depth: usize) {
+        self.enter(Key::Native(word.id()), depth);
+    }
+
+    /// the word that started with `depth` frames returns
+    pub fn exit(&mut self, depth: usize) {
+        if self
+            .active
+            .last()
+            .is_none_or(|active| active.depth != depth)
+        {
+            return;
+        }
+        let Some(active) = self.active.pop() else {
+            return;
+        };
+        let elapsed = active.start.elapsed();
+        let exclusive = elapsed.saturating_sub(active.children);
+        if let Some(parent) = self.active.last_mut() {
+            parent.children += elapsed;
+        }
+        self.nodes[active.node].exclusive += exclusive;
+
+        let running = self.running.entry(active.key.clone()).or_default();
+        *running = running.saturating_sub(1);
+        let outermost = *running == 0;
+        let totals = self.totals.entry(active.key).or_default();
+        totals.exclusive += exclusive;
+        if outermost {
+            totals.inclusive += elapsed;
+        }
+    }
+
+    /// execution stopped with an error, ends all words above `depth` frames
+    pub fn unwind(&mut self, depth: usize) {
+        while let Some(active) = self.active.last() {
+            if active.depth <= depth {
+                break;
+            }
+            let depth = active.depth;
+            self.exit(depth);
+        }
+    }
+
+    /// names of the native words in `dictionary`
+    fn names(dictionary: &Dictionary) -> HashMap<usize, String> {
+        let mut names: HashMap<usize, String> = HashMap::new();
+        // compiled by `DO`, not a word of its own
+        names.insert(runtime_do as *const () as usize, "do".to_owned());
+        for (name, word) in SUPERINSTRUCTIONS {
+            names.insert(word as *const () as usize, name.to_owned());
+        }
+        for name in dictionary.names() {
+            if let Ok(code) = dictionary.get(name) {
+                let key = match &code[..] {
+                    [Cell::Exec(word) | Cell::Compiled(word)] => *word as usize,
+                    [Cell::Closure(word)] => word.id(),
+                    _ => continue,
+                };
+                let name = names
+                    .get(&key)
+                    .map_or(name, |known| known.as_str().min(name));
+                names.insert(key, name.to_owned());
+            }
+        }
+        names
+    }
+
+    fn name(names: &HashMap<usize, String>, key: &Key) -> String {
+        match key {
+            Key::Word(name) if name.is_empty() => "<anonymous>".to_owned(),
+            Key::Word(name) => name.to_lowercase(),
+            Key::Native(word) => names.get(word).cloned().unwrap_or("<native>".to_owned()),
+        }
+    }
+
+    /// all words, the most expensive first
+    pub fn report(&self, dictionary: &Dictionary) -> Vec<WordProfile> {
+        let names = Profiler::names(dictionary);
+        let mut words: HashMap<String, WordProfile> = HashMap::new();
+        for (key, totals) in &self.totals {
+            let name = Profiler::name(&names, key);
+            let word = words.entry(name.clone()).or_insert(WordProfile {
+                name,
+                calls: 0,
+                inclusive: Duration::ZERO,
+                exclusive: Duration::ZERO,
+            });
+            word.calls += totals.calls;
+            word.inclusive += totals.inclusive;
+            word.exclusive += totals.exclusive;
+        }
+        let mut report: Vec<WordProfile> = words.into_values().collect();
+        report.sort_by(|a, b| {
+            b.inclusive
+                .cmp(&a.inclusive)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        report
+    }
+
+    /// exclusive nanoseconds per call path, one `a;b;c 1234` line each
+    ///
+    /// the format of `flamegraph.pl` and `inferno`
+    pub fn collapsed(&self, dictionary: &Dictionary) -> String {
+        let names = Profiler::names(dictionary);
+        let mut paths: HashMap<String, u128> = HashMap::new();
+        for node in &self.nodes {
+            let mut path = vec![Profiler::name(&names, &node.key)];
+            let mut parent = node.parent;
+            while let Some(index) = parent {
+                path.push(Profiler::name(&names, &self.nodes[index].key));
+                parent = self.nodes[index].parent;
+            }
+            path.reverse();
+            *paths.entry(path.join(";")).or_default() += node.exclusive.as_nanos();
+        }
+        let mut lines: Vec<String> = paths
+            .into_iter()
+            .map(|(path, nanos)| format!("{} {}\n", path, nanos))
+            .collect();
+        lines.sort();
+        lines.concat()
+    }
+}
+
+/// forth `profile` command
+///
+/// - takes the next token as the name of a word
+/// - runs it with the profiler switched on, see `.profile`
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_tools(&mut vm.dictionary);
+///
+/// vm.eval(": sq dup * ; 3 profile sq 4 profile sq");
+///
+/// assert!(!vm.profiler.enabled);
+/// assert_eq!(vm.profiler.report(&vm.dictionary)[0].calls, 2);
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(16)));
+/// ```
+pub fn profile(vm: &mut VM) -> Result<()> {
+    let name = vm
+        .input_buffer
+        .pop_front()
+        .ok_or(Error::Parser("EOL".to_owned()))?;
+    let word = vm.lookup(&name)?;
+    let enabled = vm.profiler.enabled;
+    vm.profiler.enabled = true;
+    let result = vm.execute_word(&name, word);
+    vm.profiler.enabled = enabled;
+    result
+}
+
+/// forth `.profile` command
+///
+/// prints calls, inclusive and exclusive time in microseconds
+/// of every word profiled so far
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_tools(&mut vm.dictionary);
+///
+/// vm.eval(": sq dup * ; 3 profile sq .profile");
+///
+/// let report = output.take();
+/// assert!(report.starts_with("     calls   inclusive   exclusive  word\n"));
+/// assert!(report.lines().any(|line| line.starts_with("         1 ") && line.ends_with("  sq")));
+/// ```
+pub fn dot_profile(vm: &mut VM) -> Result<()> {
+    let mut text = format!(
+        "{:>10}  {:>10}  {:>10}  word\n",
+        "calls", "inclusive", "exclusive"
+    );
+    for word in vm.profiler.report(&vm.dictionary) {
+        text.push_str(&format!(
+            "{:>10}  {:>10.1}  {:>10.1}  {}\n",
+            word.calls,
+            word.inclusive.as_secs_f64() * 1e6,
+            word.exclusive.as_secs_f64() * 1e6,
+            word.name
+        ));
+    }
+    vm.write(&text)
+}
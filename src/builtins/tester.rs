@@ -0,0 +1,250 @@
+use crate::{Error, Result, Variable, VM};
+use std::collections::HashMap;
+
+/// pass and fail counts of the tests of one word
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Count {
+    /// the word under test, or the `TESTING` section if
+    /// a test uses none of the words the section names
+    pub name: String,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+/// state of the Hayes tester words
+///
+/// - the test that is currently running
+/// - results of all tests so far, counted per word under test
+#[derive(Debug, Default)]
+pub struct Tester {
+    /// stack depth at `T{`, `None` outside of a test
+    start: Option<usize>,
+    /// stack depth and results at `->`
+    actual: Option<(usize, Vec<Variable>)>,
+    /// source of the running test for failure messages
+    source: String,
+    /// name of the running `TESTING` section
+    section: String,
+    /// words the running section tests, the names after the `:` if there is one
+    words: Vec<String>,
+    /// what the running test is counted for
+    word: String,
+    /// uppercase names of words defined in tests, with the word they test
+    helpers: HashMap<String, String>,
+    /// words in the order they were first tested
+    pub counts: Vec<Count>,
+}
+
+impl Tester {
+    /// total number of passed tests
+    pub fn passed(&self) -> usize {
+        self.counts.iter().map(|count| count.passed).sum()
+    }
+
+    /// total number of failed tests
+    pub fn failed(&self) -> usize {
+        self.counts.iter().map(|count| count.failed).sum()
+    }
+
+    /// counts a test that never reached `}T` as failed
+    ///
+    /// this happens if an error aborted the line of the test
+    pub fn close(&mut self) {
+        if self.start.take().is_some() {
+            self.actual = None;
+            self.current().failed += 1;
+        }
+    }
+
+    /// table of pass and fail counts per word
+    ///
+    /// a test is counted for the first word of its section it uses,
+    /// directly or through a word defined in an earlier test
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// builtins::add_tester(&mut vm.dictionary);
+    ///
+    /// vm.eval("TESTING ARITHMETIC: + -");
+    /// vm.eval("T{ 1 2 + -> 3 }T");
+    /// vm.eval("T{ 1 2 - -> 1 }T");
+    /// vm.eval("T{ 1 2 + 3 - -> 0 }T");
+    /// vm.eval("T{ 1 2 swap -> 2 1 }T");
+    /// assert_eq!(vm.tester.report(), "\
+    /// passed failed  word
+    ///      2      0  +
+    ///      0      1  -
+    ///      1      0  ARITHMETIC: + -
+    ///      3      1  total
+    /// ");
+    /// ```
+    pub fn report(&self) -> String {
+        let mut report = String::from("passed failed  word\n");
+        for count in &self.counts {
+            report.push_str(&format!(
+                "{:6} {:6}  {}\n",
+                count.passed, count.failed, count.name
+            ));
+        }
+        report.push_str(&format!("{:6} {:6}  total\n", self.passed(), self.failed()));
+        report
+    }
+
+    /// the counts of the running test
+    fn current(&mut self) -> &mut Count {
+        let index = match self.counts.iter().position(|count| count.name == self.word) {
+            Some(index) => index,
+            None => {
+                self.counts.push(Count {
+                    name: self.word.clone(),
+                    ..Count::default()
+                });
+                self.counts.len() - 1
+            }
+        };
+        &mut self.counts[index]
+    }
+}
+
+/// pops everything above `depth`, deepest value first
+fn pop_above(vm: &mut VM, depth: usize) -> Result<Vec<Variable>> {
+    let mut values = Vec::new();
+    while vm.value_stack.len() > depth {
+        values.push(vm.value_stack.pop()?);
+    }
+    values.reverse();
+    Ok(values)
+}
+
+/// tester `T{` command
+///
+/// - starts a test and remembers the stack depth
+/// - the rest of the test up to `}T` is kept for failure messages
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+/// builtins::add_core(&mut vm.dictionary);
+/// builtins::add_tester(&mut vm.dictionary);
+///
+/// vm.eval("7 T{ 1 1 + -> 2 }T");
+/// vm.eval("T{ 1 1 + -> 3 }T");
+/// vm.eval("T{ 1 1 -> 1 }T");
+/// assert_eq!(output.take(), "\
+/// INCORRECT RESULT: T{ 1 1 + -> 3 }T
+/// WRONG NUMBER OF RESULTS: T{ 1 1 -> 1 }T
+/// ");
+/// assert_eq!(vm.tester.passed(), 1);
+/// assert_eq!(vm.tester.failed(), 2);
+/// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(7)));
+/// assert!(vm.value_stack.is_empty());
+/// ```
+pub fn t_open(vm: &mut VM) -> Result<()> {
+    vm.tester.close();
+    let mut source = vec!["T{"];
+    for token in vm.input_buffer.iter() {
+        source.push(token);
+        if token.eq_ignore_ascii_case("}t") {
+            break;
+        }
+    }
+    let tester = &mut vm.tester;
+    tester.word = source
+        .iter()
+        .find_map(|token| {
+            let token = token.to_uppercase();
+            tester
+                .words
+                .iter()
+                .find(|word| word.to_uppercase() == token)
+                .or(tester.helpers.get(&token))
+        })
+        .unwrap_or(&tester.section)
+        .clone();
+    // `T{ : name ... ; -> }T` defines a word that later tests use
+    for pair in source.windows(2) {
+        if pair[0] == ":" && tester.word != tester.section {
+            let word = tester.word.clone();
+            tester.helpers.insert(pair[1].to_uppercase(), word);
+        }
+    }
+    tester.source = source.join(" ");
+    tester.start = Some(vm.value_stack.len());
+    Ok(())
+}
+
+/// tester `->` command
+///
+/// - takes the actual results of the test from the stack
+pub fn t_arrow(vm: &mut VM) -> Result<()> {
+    let start = vm.tester.start.ok_or(Error::Parser("->".to_owned()))?;
+    let depth = vm.value_stack.len();
+    let values = pop_above(vm, start)?;
+    vm.tester.actual = Some((depth, values));
+    Ok(())
+}
+
+/// tester `}T` command
+///
+/// - takes the expected results from the stack
+/// - compares them with the actual results and counts the test
+pub fn t_close(vm: &mut VM) -> Result<()> {
+    let start = vm.tester.start.ok_or(Error::Parser("}T".to_owned()))?;
+    let (depth, actual) = vm
+        .tester
+        .actual
+        .take()
+        .ok_or(Error::Parser("}T".to_owned()))?;
+    let correct_depth = vm.value_stack.len() == depth;
+    let expected = pop_above(vm, start)?;
+    vm.tester.start = None;
+
+    let failure = if !correct_depth {
+        Some("WRONG NUMBER OF RESULTS")
+    } else if expected != actual {
+        Some("INCORRECT RESULT")
+    } else {
+        None
+    };
+    match failure {
+        Some(message) => {
+            vm.tester.current().failed += 1;
+            let text = format!("{}: {}\n", message, vm.tester.source);
+            vm.write(&text)
+        }
+        None => {
+            vm.tester.current().passed += 1;
+            Ok(())
+        }
+    }
+}
+
+/// tester `TESTING` command
+///
+/// - the rest of the line names the section for the following tests
+/// - the words after a `:`, or all of them, are the words under test
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_tester(&mut vm.dictionary);
+///
+/// vm.eval("TESTING basic assumptions");
+/// vm.eval("T{ -> }T");
+/// assert_eq!(vm.tester.counts[0].name, "basic assumptions");
+/// assert_eq!(vm.tester.counts[0].passed, 1);
+/// ```
+pub fn testing(vm: &mut VM) -> Result<()> {
+    vm.tester.close();
+    let name: Vec<String> = vm.input_buffer.drain(..).collect();
+    let words = match name.iter().position(|token| token.ends_with(':')) {
+        Some(colon) => &name[colon + 1..],
+        None => &name[..],
+    };
+    vm.tester.words = words.to_vec();
+    vm.tester.section = name.join(" ");
+    Ok(())
+}
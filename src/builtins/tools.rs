@@ -1,11 +1,39 @@
-use crate::Result;
-use crate::VM;
+use crate::{Error, Result, Variable, VM};
 
 pub fn dot_s(vm: &mut VM) -> Result<()> {
-    (vm.write)(&format!(" <{}> ", vm.value_stack.len()));
+    let mut text = format!(" <{}> ", vm.value_stack.len());
     for value in vm.value_stack.iter() {
-        (vm.write)(&format!(" {} ", value));
+        text.push_str(&format!(" {} ", value));
+    }
+    text.push('\n');
+    vm.write(&text)
+}
+
+/// forth `bye` command
+///
+/// https://forth-standard.org/standard/tools/BYE
+///
+/// ends `eval` with `Error::Bye`, the host decides how to leave
+///
+/// - a number on top of the stack is popped as the exit code, `0` otherwise
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_tools(&mut vm.dictionary);
+///
+/// assert_eq!(vm.eval("bye"), Err(Error::Bye(0)));
+/// vm.cancel();
+/// assert_eq!(vm.eval("1 2 3 bye 4"), Err(Error::Bye(3)));
+/// assert_eq!(vm.value_stack.len(), 2);
+/// ```
+pub fn bye(vm: &mut VM) -> Result<()> {
+    match vm.value_stack.at(0) {
+        Ok(Variable::Int(code)) => {
+            let code = *code;
+            vm.value_stack.pop()?;
+            Err(Error::Bye(code))
+        }
+        _ => Err(Error::Bye(0)),
     }
-    (vm.write)(&format!("\n"));
-    Ok(())
 }
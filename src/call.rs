@@ -0,0 +1,205 @@
+use crate::{Error, Result, Stack, Variable};
+
+/// a rust value that can be pushed, see `VM::call`
+///
+/// a number that doesn't fit in an `i64` is an `Error::Type`
+///
+/// ```
+/// # use frust::*;
+/// assert_eq!(3usize.into_variable(), Ok(Variable::Int(3)));
+/// assert_eq!(u64::MAX.into_variable(), Err(Error::Type));
+/// assert_eq!(vec![u64::MAX].into_variable(), Err(Error::Type));
+/// ```
+pub trait IntoVariable {
+    fn into_variable(self) -> Result<Variable>;
+}
+
+/// a rust value that can be popped, see `VM::call`
+///
+/// a value of an other kind is an `Error::Type`
+///
+/// ```
+/// # use frust::*;
+/// assert_eq!(u8::from_variable(Variable::Int(200)), Ok(200));
+/// assert_eq!(u8::from_variable(Variable::Int(1000)), Err(Error::Type));
+/// assert_eq!(usize::from_variable(Variable::Int(7)), Ok(7));
+/// assert_eq!(u64::from_variable(Variable::Int(-1)), Err(Error::Type));
+/// assert_eq!(bool::from_variable(Variable::Int(3)), Ok(true));
+/// assert_eq!(String::from_variable(Variable::Int(3)), Err(Error::Type));
+/// assert_eq!(
+///     Vec::<Vec<i32>>::from_variable(vec![vec![1, 2], vec![3]].into_variable().unwrap()),
+///     Ok(vec![vec![1, 2], vec![3]])
+/// );
+/// ```
+pub trait FromVariable: Sized {
+    fn from_variable(value: Variable) -> Result<Self>;
+}
+
+/// arguments of `VM::call`, a single value or a tuple pushed from left to right
+///
+/// nothing is pushed if one of the values can't be
+pub trait IntoStack {
+    /// number of values pushed
+    const COUNT: usize;
+    fn push_to(self, stack: &mut Stack) -> Result<()>;
+}
+
+/// results of `VM::call`, a single value or a tuple with the top of the stack last
+///
+/// ```
+/// # use frust::*;
+/// let mut stack = Stack::new();
+/// (String::from("a"), 2, Variable::Int(3)).push_to(&mut stack).unwrap();
+/// assert_eq!(stack.at(0), Ok(&Variable::Int(3)));
+///
+/// assert_eq!(
+///     <(String, i16, Variable)>::pop_from(&mut stack),
+///     Ok(("a".to_owned(), 2, Variable::Int(3)))
+/// );
+/// assert_eq!(<(i64,)>::pop_from(&mut stack), Err(Error::Stack));
+/// ```
+pub trait FromStack: Sized {
+    /// number of values popped
+    const COUNT: usize;
+    fn pop_from(stack: &mut Stack) -> Result<Self>;
+}
+
+impl IntoVariable for Variable {
+    fn into_variable(self) -> Result<Variable> {
+        Ok(self)
+    }
+}
+impl FromVariable for Variable {
+    fn from_variable(value: Variable) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+macro_rules! integer {
+    ($($int:ty),*) => {$(
+        impl IntoVariable for $int {
+            fn into_variable(self) -> Result<Variable> {
+                i64::try_from(self).map(Variable::Int).map_err(|_| Error::Type)
+            }
+        }
+        impl FromVariable for $int {
+            fn from_variable(value: Variable) -> Result<Self> {
+                match value {
+                    Variable::Int(value) => <$int>::try_from(value).map_err(|_| Error::Type),
+                    _ => Err(Error::Type),
+                }
+            }
+        }
+    )*};
+}
+integer!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+/// flags are `-1` for true and `0` for false, any other number is true as well
+impl IntoVariable for bool {
+    fn into_variable(self) -> Result<Variable> {
+        Ok(Variable::Int(if self { -1 } else { 0 }))
+    }
+}
+impl FromVariable for bool {
+    fn from_variable(value: Variable) -> Result<Self> {
+        match value {
+            Variable::Int(flag) => Ok(flag != 0),
+            _ => Err(Error::Type),
+        }
+    }
+}
+
+impl IntoVariable for String {
+    fn into_variable(self) -> Result<Variable> {
+        Ok(Variable::String(self))
+    }
+}
+impl IntoVariable for &str {
+    fn into_variable(self) -> Result<Variable> {
+        Ok(Variable::String(self.to_owned()))
+    }
+}
+impl FromVariable for String {
+    fn from_variable(value: Variable) -> Result<Self> {
+        match value {
+            Variable::String(text) => Ok(text),
+            _ => Err(Error::Type),
+        }
+    }
+}
+
+impl<T: IntoVariable> IntoVariable for Vec<T> {
+    fn into_variable(self) -> Result<Variable> {
+        let values = self.into_iter().map(T::into_variable);
+        values.collect::<Result<_>>().map(Variable::Array)
+    }
+}
+impl<T: FromVariable> FromVariable for Vec<T> {
+    fn from_variable(value: Variable) -> Result<Self> {
+        match value {
+            Variable::Array(values) => values.into_iter().map(T::from_variable).collect(),
+            _ => Err(Error::Type),
+        }
+    }
+}
+
+impl<T: IntoVariable> IntoStack for T {
+    const COUNT: usize = 1;
+    fn push_to(self, stack: &mut Stack) -> Result<()> {
+        stack.push(self.into_variable()?);
+        Ok(())
+    }
+}
+impl<T: FromVariable> FromStack for T {
+    const COUNT: usize = 1;
+    fn pop_from(stack: &mut Stack) -> Result<Self> {
+        T::from_variable(stack.pop()?)
+    }
+}
+
+impl IntoStack for () {
+    const COUNT: usize = 0;
+    fn push_to(self, _stack: &mut Stack) -> Result<()> {
+        Ok(())
+    }
+}
+impl FromStack for () {
+    const COUNT: usize = 0;
+    fn pop_from(_stack: &mut Stack) -> Result<Self> {
+        Ok(())
+    }
+}
+
+macro_rules! tuple {
+    ($count:literal: $($name:ident),*) => {
+        impl<$($name: IntoVariable),*> IntoStack for ($($name,)*) {
+            const COUNT: usize = $count;
+            #[allow(non_snake_case)]
+            fn push_to(self, stack: &mut Stack) -> Result<()> {
+                let ($($name,)*) = self;
+                $(let $name = $name.into_variable()?;)*
+                $(stack.push($name);)*
+                Ok(())
+            }
+        }
+        impl<$($name: FromVariable),*> FromStack for ($($name,)*) {
+            const COUNT: usize = $count;
+            #[allow(non_snake_case)]
+            fn pop_from(stack: &mut Stack) -> Result<Self> {
+                // the last one is on top, pop them in reverse
+                let mut values = Vec::with_capacity($count);
+                for _ in 0..$count {
+                    values.push(stack.pop()?);
+                }
+                let mut values = values.into_iter().rev();
+                Ok(($($name::from_variable(values.next().ok_or(Error::Stack)?)?,)*))
+            }
+        }
+    };
+}
+tuple!(1: A);
+tuple!(2: A, B);
+tuple!(3: A, B, C);
+tuple!(4: A, B, C, D);
+tuple!(5: A, B, C, D, E);
+tuple!(6: A, B, C, D, E, F);
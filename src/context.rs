@@ -1,10 +1,15 @@
-use crate::{Cell, Dictionary, Error, Result, Stack, Variable};
+use crate::{
+    builtins::{self, Debugger, Files, Profiler, Tester, WordSet},
+    optimize, Cell, Code, Diagnostics, Dictionary, Error, FromStack, Hooks, Input, IntoStack,
+    Output, Result, Sandbox, Severity, Stack, Step, Variable,
+};
 use std::{
     any::Any,
     collections::VecDeque,
     fmt::Debug,
-    io::{BufRead, Write},
+    io::{BufRead, BufWriter, Write},
     mem,
+    sync::Arc,
 };
 
 #[derive(Debug, Default)]
@@ -32,22 +37,116 @@ pub struct VM {
     pub value_stack: Stack,
     pub return_stack: Stack,
     pub dictionary: Dictionary,
-    pub write: Box<dyn Fn(&str)>,
-    pub read: Box<dyn Fn(&mut String) -> std::io::Result<usize>>,
+    pub input: Input,
+    pub output: Output,
+    pub diagnostics: Diagnostics,
+    /// number of errors reported to `diagnostics` so far
+    pub error_count: usize,
     pub state: State,
     pub handle_errors: bool,
     pub input_buffer: VecDeque<String>,
+    /// characters read from `input` but not yet consumed by `KEY` or `ACCEPT`
+    pub key_buffer: VecDeque<char>,
+    /// open files and include bookkeeping of the file access word set
+    pub files: Files,
+    /// results of the tester word set
+    pub tester: Tester,
+    /// breakpoints and stepping of `DEBUG`
+    pub debugger: Debugger,
+    /// calls and time per word, see `PROFILE`
+    pub profiler: Profiler,
+    /// definitions that are being executed, innermost last
+    pub frames: Vec<Frame>,
+    /// maximum number of `frames` before `Error::ReturnStackOverflow`
+    pub max_call_depth: usize,
+    /// reuse the frame of the caller for calls in tail position,
+    /// switch off to keep every frame for debugging
+    pub tail_calls: bool,
+    /// run the peephole `optimize`r over new definitions,
+    /// switch off to see definitions cell by cell while debugging
+    pub optimize: bool,
+    /// called by the inner interpreter for tracing, profiling or coverage,
+    /// wrap hooks that are not `Send` in a `Local`
+    pub hooks: Option<Box<dyn Hooks + Send>>,
+    /// cells the inner interpreter may still run, `None` for no limit.
+    /// when it is used up `eval` stops with `Error::Yield`
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.optimize = false;
+    /// vm.eval(": f 1 2 + ;").unwrap();
+    ///
+    /// // every cell costs one
+    /// vm.fuel = Some(3);
+    /// assert_eq!(vm.eval("f"), Ok(()));
+    /// assert_eq!(vm.fuel, Some(0));
+    ///
+    /// vm.fuel = Some(2);
+    /// assert_eq!(vm.eval("f"), Err(Error::Yield));
+    /// assert_eq!(vm.value_stack.len(), 3);
+    /// ```
+    pub fuel: Option<u64>,
+    /// denied words and quotas for untrusted scripts
+    pub sandbox: Sandbox,
+    /// state of the host for its native words, see `user_data`
+    pub user_data: Option<Box<dyn Any + Send>>,
+}
+/// default for `VM::max_call_depth`
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1 << 16;
+
+/// true if nothing but returning is left to do from `pc` on
+///
+/// unconditional branches forward are followed, so calls at the
+/// end of an `if` branch are in tail position as well
+fn is_tail(code: &[Cell], mut pc: usize) -> bool {
+    loop {
+        match (code.get(pc), code.get(pc + 1)) {
+            (None, _) | (Some(Cell::ControlReturn), _) => return true,
+            (Some(Cell::ControlBranch(step)), _) if *step > 0 => {
+                pc = pc.saturating_add(*step as usize)
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// a definition that is being executed
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// name of the word, empty for anonymous programs
+    pub name: Arc<str>,
+    pub code: Code,
+    /// index of the next cell to execute
+    pub pc: usize,
 }
+
 impl Debug for VM {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("VM")
             .field("value_stack", &self.value_stack)
             .field("return_stack", &self.return_stack)
-            .field("write", &self.write.type_id())
-            .field("read", &self.write.type_id())
+            .field("input", &"dyn BufRead")
+            .field("output", &"dyn Write")
+            .field("diagnostics", &"dyn Write")
+            .field("error_count", &self.error_count)
             .field("state", &self.state)
             .field("handle_errors", &self.handle_errors)
             .field("input_buffer", &self.input_buffer)
+            .field("key_buffer", &self.key_buffer)
+            .field("files", &self.files)
+            .field("tester", &self.tester)
+            .field("debugger", &self.debugger)
+            .field("profiler", &self.profiler)
+            .field("frames", &self.frames)
+            .field("max_call_depth", &self.max_call_depth)
+            .field("tail_calls", &self.tail_calls)
+            .field("optimize", &self.optimize)
+            .field("hooks", &self.hooks.as_ref().map(|_| "dyn Hooks"))
+            .field("fuel", &self.fuel)
+            .field("sandbox", &self.sandbox)
+            .field("user_data", &self.user_data.as_ref().map(|_| "dyn Any"))
             .finish()
     }
 }
@@ -56,65 +155,426 @@ impl VM {
     /// Create a new context and bind
     /// input - `stdin`
     /// output - `stdout`
+    /// diagnostics - `stderr`
     /// ```no_run
     /// # use frust::VM;
-    /// let vm = VM::new_stdio();
+    /// let mut vm = VM::new_stdio();
     ///
-    /// /// read data from stdin
-    /// let mut buffer = String::new();
-    /// (vm.read)(&mut buffer);
+    /// /// read a line from stdin
+    /// let line = vm.read_line().unwrap().unwrap_or_default();
     ///
     /// /// write data to stdout
-    /// (vm.write)(&buffer);
+    /// vm.write(&line);
+    /// vm.flush();
     /// ```
     pub fn new_stdio() -> VM {
-        Self::new(
-            |buf| {
-                let stdin = std::io::stdin();
-                stdin.lock().read_line(buf)
-            },
-            |buf| {
-                print!("{} ", buf);
-                std::io::stdout().flush().unwrap();
-            },
-        )
+        // a `StdinLock` can't be sent to an other thread
+        Self::new(std::io::BufReader::new(std::io::stdin()), std::io::stdout())
     }
 
     /// Create a new context and bind
     /// input - `null`
     /// output - `null`
+    /// diagnostics - `null`
     ///
     /// ```
     /// # use frust::VM;
-    /// let vm = VM::new_null();
+    /// let mut vm = VM::new_null();
     ///
     /// /// read nothing
-    /// let mut buffer = String::new();
-    /// (vm.read)(&mut buffer);
+    /// assert_eq!(vm.read_line(), Ok(None));
     ///
     /// /// write nothing
-    /// (vm.write)(&buffer);
+    /// assert_eq!(vm.write("nothing"), Ok(()));
     /// ```
     pub fn new_null() -> VM {
-        Self::new(|_| Ok(0), |_| {})
+        let mut vm = Self::new(std::io::empty(), std::io::sink());
+        vm.diagnostics = Box::new(std::io::sink());
+        vm
     }
 
     /// Create a new forth VM
-    /// `read` - global user input function
-    /// `write` - global write to user function
-    pub fn new(read: fn(&mut String) -> std::io::Result<usize>, write: fn(&str)) -> VM {
+    /// `input` - global user input, any `BufRead`
+    /// `output` - global output to the user, any `Write`
+    ///
+    /// both have to be `Send` so the VM can move to an other thread,
+    /// wrap them in a `Local` otherwise
+    ///
+    /// diagnostics go to `stderr` until `diagnostics` is replaced
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let output = Capture::new();
+    /// let mut vm = VM::new("2 3".as_bytes(), output.clone());
+    /// # vm.dictionary.add("+", Cell::Exec(builtins::plus));
+    /// # vm.dictionary.add(".", Cell::Exec(builtins::dot));
+    ///
+    /// let line = vm.read_line().unwrap().unwrap();
+    /// vm.eval(&line);
+    /// vm.eval("+ .");
+    ///
+    /// assert_eq!(output.take(), "5 ");
+    ///
+    /// fn send<T: Send>(_: &T) {}
+    /// send(&vm);
+    /// ```
+    pub fn new<R, W>(input: R, output: W) -> VM
+    where
+        R: BufRead + Send + 'static,
+        W: Write + Send + 'static,
+    {
         VM {
             value_stack: Stack::new(),
             return_stack: Stack::new(),
             dictionary: Dictionary::new(),
-            write: Box::new(write),
-            read: Box::new(read),
+            input: Box::new(input),
+            output: BufWriter::new(Box::new(output)),
+            diagnostics: Box::new(std::io::stderr()),
+            error_count: 0,
             state: State::Interpret,
             handle_errors: true,
             input_buffer: VecDeque::new(),
+            key_buffer: VecDeque::new(),
+            files: Files::default(),
+            tester: Tester::default(),
+            debugger: Debugger::default(),
+            profiler: Profiler::default(),
+            frames: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            tail_calls: true,
+            optimize: true,
+            hooks: None,
+            fuel: None,
+            sandbox: Sandbox::default(),
+            user_data: None,
         }
     }
 
+    /// write to the user
+    ///
+    /// the output is buffered, see `flush`
+    pub fn write(&mut self, text: &str) -> Result<()> {
+        self.output
+            .write_all(text.as_bytes())
+            .map_err(|error| Error::Io(error.to_string()))
+    }
+
+    /// flush all buffered output
+    ///
+    /// errors of the output backend are reported as `Error::Io`
+    ///
+    /// ```
+    /// # use frust::*;
+    /// struct Broken;
+    /// impl std::io::Write for Broken {
+    ///     fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+    ///         Err(std::io::ErrorKind::BrokenPipe.into())
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    /// let mut vm = VM::new(std::io::empty(), Broken);
+    ///
+    /// assert_eq!(vm.write("lost"), Ok(()));
+    /// assert!(matches!(vm.flush(), Err(Error::Io(_))));
+    /// ```
+    pub fn flush(&mut self) -> Result<()> {
+        self.output
+            .flush()
+            .map_err(|error| Error::Io(error.to_string()))
+    }
+
+    /// report an error or warning to `diagnostics`
+    ///
+    /// diagnostics are best effort, a failing backend is ignored.
+    /// pending output is flushed first to keep both in order on a terminal.
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let output = Capture::new();
+    /// let diagnostics = Capture::new();
+    /// let mut vm = VM::new(std::io::empty(), output.clone());
+    /// vm.diagnostics = Box::new(diagnostics.clone());
+    /// # vm.dictionary.add(".", Cell::Exec(builtins::dot));
+    ///
+    /// vm.eval("1 . foo 2 .");
+    ///
+    /// assert_eq!(output.take(), "1 2 ");
+    /// assert_eq!(diagnostics.take(), "Error: Parsing failed (\"foo\")\n");
+    /// assert_eq!(vm.error_count, 1);
+    ///
+    /// vm.eval(": one 1 ;");
+    /// vm.eval(": one 1 ;");
+    /// assert_eq!(diagnostics.take(), "Warning: redefined one\n");
+    /// assert_eq!(vm.error_count, 1);
+    /// ```
+    pub fn diagnose(&mut self, severity: Severity, message: &str) {
+        let _ = self.flush();
+        let _ = writeln!(self.diagnostics, "{}: {}", severity, message);
+        let _ = self.diagnostics.flush();
+        if severity == Severity::Error {
+            self.error_count += 1;
+        }
+    }
+
+    /// reads the next line from `input` into the `key_buffer`
+    /// returns `false` if there is no more input
+    fn fill_key_buffer(&mut self) -> Result<bool> {
+        // make sure prompts are visible before we block on input
+        self.flush()?;
+        let mut buffer = String::new();
+        match self.input.read_line(&mut buffer) {
+            Ok(0) => Ok(false),
+            Ok(_) => {
+                self.key_buffer.extend(buffer.chars());
+                Ok(true)
+            }
+            Err(error) => Err(Error::Io(error.to_string())),
+        }
+    }
+
+    /// read a single character from the user
+    ///
+    /// takes pending characters first and only calls `read` if there are none left.
+    /// returns `None` at the end of the input
+    ///
+    /// ```
+    /// # use frust::VM;
+    /// let mut vm = VM::new("ab\n".as_bytes(), std::io::sink());
+    ///
+    /// assert_eq!(vm.read_key(), Ok(Some('a')));
+    /// assert_eq!(vm.read_key(), Ok(Some('b')));
+    /// assert_eq!(vm.read_key(), Ok(Some('\n')));
+    /// ```
+    pub fn read_key(&mut self) -> Result<Option<char>> {
+        if self.key_buffer.is_empty() && !self.fill_key_buffer()? {
+            return Ok(None);
+        }
+        Ok(self.key_buffer.pop_front())
+    }
+
+    /// read a line from the user without the line terminator
+    ///
+    /// takes pending characters first and only calls `read` if there are none left.
+    /// returns `None` at the end of the input
+    ///
+    /// ```
+    /// # use frust::VM;
+    /// let mut vm = VM::new("hello world\n".as_bytes(), std::io::sink());
+    ///
+    /// assert_eq!(vm.read_key(), Ok(Some('h')));
+    /// assert_eq!(vm.read_line(), Ok(Some("ello world".to_owned())));
+    /// ```
+    pub fn read_line(&mut self) -> Result<Option<String>> {
+        if self.key_buffer.is_empty() && !self.fill_key_buffer()? {
+            return Ok(None);
+        }
+        let mut line = String::new();
+        while let Some(key) = self.key_buffer.pop_front() {
+            if key == '\n' {
+                break;
+            }
+            line.push(key);
+        }
+        if line.ends_with('\r') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    /// the `user_data` as a `T`
+    ///
+    /// `Error::Type` if there is none or it is something else
+    ///
+    /// ```
+    /// # use frust::*;
+    /// struct Config {
+    ///     greeting: String,
+    /// }
+    ///
+    /// let output = Capture::new();
+    /// let mut vm = VM::new(std::io::empty(), output.clone());
+    /// vm.dictionary.add("greet", Cell::Exec(|vm| {
+    ///     let text = vm.user_data::<Config>()?.greeting.clone();
+    ///     vm.write(&text)
+    /// }));
+    ///
+    /// assert_eq!(vm.user_data::<Config>().err(), Some(Error::Type));
+    /// vm.user_data = Some(Box::new(Config { greeting: "hello".to_owned() }));
+    /// vm.eval("greet").unwrap();
+    /// assert_eq!(output.take(), "hello");
+    /// assert_eq!(vm.user_data::<String>().err(), Some(Error::Type));
+    /// ```
+    pub fn user_data<T: Any>(&self) -> Result<&T> {
+        self.user_data
+            .as_deref()
+            .and_then(|data| data.downcast_ref())
+            .ok_or(Error::Type)
+    }
+
+    /// the `user_data` as a mutable `T`
+    pub fn user_data_mut<T: Any>(&mut self) -> Result<&mut T> {
+        self.user_data
+            .as_deref_mut()
+            .and_then(|data| data.downcast_mut())
+            .ok_or(Error::Type)
+    }
+
+    /// body of the word `name`, unless the `sandbox` denies it
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_all(&mut vm.dictionary);
+    /// vm.sandbox.deny("bye");
+    ///
+    /// assert!(vm.lookup("dup").is_ok());
+    /// assert_eq!(vm.lookup("BYE"), Err(Error::Denied("BYE".to_owned())));
+    /// ```
+    pub fn lookup(&self, name: &str) -> Result<Code> {
+        if self.sandbox.denies(name) {
+            return Err(Error::Denied(name.to_owned()));
+        }
+        self.dictionary.get(name)
+    }
+
+    /// adds the words of `set` the `sandbox` doesn't deny
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// vm.sandbox.deny_set(builtins::WordSet::Tools);
+    /// vm.sandbox.allow(".s");
+    /// vm.add_word_set(builtins::WordSet::Tools);
+    ///
+    /// assert_eq!(vm.dictionary.names().collect::<Vec<_>>(), [".s"]);
+    /// ```
+    pub fn add_word_set(&mut self, set: WordSet) {
+        let mut words = Dictionary::new();
+        set.add(&mut words);
+        for name in words.names() {
+            if let (false, Ok(code)) = (self.sandbox.denies(name), words.get(name)) {
+                self.dictionary.add(name, code.to_vec());
+            }
+        }
+    }
+
+    /// calls the word `name` with typed arguments and results
+    ///
+    /// the word is looked up in the dictionary, no source is parsed.
+    /// it must take all `args` and leave exactly the results, else the call
+    /// fails with `Error::Arity` and whatever it left is dropped. results
+    /// of the wrong kind are an `Error::Type`.
+    ///
+    /// errors of the word are returned as they are, without `handle_errors`,
+    /// and what it left is dropped too. a word that takes more than `args`
+    /// fails with `Error::Arity` of the number of `args` and of the values
+    /// it took. after `Error::Yield` the results are on
+    /// the stack once `resume` is done.
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.eval(": squares dup * swap dup * + ;").unwrap();
+    ///
+    /// assert_eq!(vm.call::<(i64, i64), i64>("squares", (3, 4)), Ok(25));
+    /// // `mod` leaves the remainder and the quotient
+    /// assert_eq!(vm.call::<_, (i32, u8)>("mod", (17, 5)), Ok((2, 3)));
+    /// assert_eq!(vm.call::<_, i64>("drop", 1), Err(Error::Arity(1, 0)));
+    /// assert_eq!(vm.call::<_, String>("dup", 1), Err(Error::Arity(1, 2)));
+    /// assert_eq!(vm.call::<_, String>("abs", -1), Err(Error::Type));
+    /// assert_eq!(vm.call::<_, i64>("/", (1, 0)), Err(Error::DivisionByZero));
+    /// assert!(vm.value_stack.is_empty());
+    ///
+    /// vm.value_stack.push(7);
+    /// assert_eq!(vm.call::<_, ()>("+", 1), Err(Error::Arity(1, 2)));
+    /// assert!(vm.value_stack.is_empty());
+    /// assert_eq!(vm.call::<_, usize>("abs", u64::MAX), Err(Error::Type));
+    /// assert_eq!(vm.call::<_, usize>("negate", 1), Err(Error::Type));
+    ///
+    /// vm.sandbox.deny("dup");
+    /// assert_eq!(vm.call::<_, i64>("dup", 1), Err(Error::Denied("dup".to_owned())));
+    /// assert_eq!(
+    ///     vm.call::<_, i64>("nope", 1),
+    ///     Err(Error::Unimplemented("nope".to_owned()))
+    /// );
+    /// ```
+    pub fn call<A, R>(&mut self, name: &str, args: A) -> Result<R>
+    where
+        A: IntoStack,
+        R: FromStack,
+    {
+        let code = self.lookup(name)?;
+        let base = self.value_stack.len();
+        args.push_to(&mut self.value_stack)?;
+        let outer = self.value_stack.lowest();
+        self.value_stack.set_lowest(base);
+        let result = self.execute_word(name, code);
+        let lowest = self.value_stack.lowest();
+        self.value_stack.set_lowest(outer.min(lowest));
+
+        let depth = self.value_stack.len();
+        let result = match result {
+            Err(Error::Yield) => return Err(Error::Yield),
+            Err(error) => Err(error),
+            // the word took values of the caller, they are gone
+            Ok(()) if lowest < base => Err(Error::Arity(A::COUNT, A::COUNT + base - lowest)),
+            Ok(()) if depth == base + R::COUNT => R::pop_from(&mut self.value_stack),
+            Ok(()) => Err(Error::Arity(R::COUNT, depth - base)),
+        };
+        // only the values of the caller the word didn't touch stay
+        while self.value_stack.len() > base.min(lowest) {
+            self.value_stack.pop()?;
+        }
+        result
+    }
+
+    /// checks the quotas of the `sandbox` for a string of `bytes`,
+    /// for natives to call before they read it
+    ///
+    /// the stacks are emptied if it doesn't fit, like for any other breach
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// vm.sandbox.max_memory = Some(1 << 16);
+    /// vm.value_stack.push(1);
+    ///
+    /// assert_eq!(vm.reserve_string(1000), Ok(()));
+    /// assert_eq!(vm.reserve_string(1 << 16), Err(Error::OutOfMemory));
+    /// assert!(vm.value_stack.is_empty());
+    /// ```
+    pub fn reserve_string(&mut self, bytes: usize) -> Result<()> {
+        if !self.sandbox.limited() {
+            return Ok(());
+        }
+        let cells = self.dictionary.cells();
+        let result = self
+            .sandbox
+            .check_string(&self.value_stack, &self.return_stack, cells, bytes);
+        if result.is_err() {
+            self.value_stack.clear();
+            self.return_stack.clear();
+        }
+        result
+    }
+
+    /// checks the quotas of the `sandbox` with `cells` in the dictionary
+    ///
+    /// the stacks are emptied unless only the dictionary is too big
+    fn check_quotas(&mut self, cells: usize) -> Result<()> {
+        let result = self
+            .sandbox
+            .check(&self.value_stack, &self.return_stack, cells);
+        if result.is_err() && result != Err(Error::DictionaryFull) {
+            self.value_stack.clear();
+            self.return_stack.clear();
+        }
+        result
+    }
+
     // actual "compilation" step
     pub fn compile(&mut self) -> Result<Vec<Cell>> {
         let mut function: Vec<Cell> = Vec::new();
@@ -122,28 +582,23 @@ impl VM {
             if token == ";" {
                 return Ok(function);
             }
+            if self.sandbox.denies(&token) {
+                return Err(Error::Denied(token));
+            }
             // if this is a valid word from our dictionary
             // add this to the function to be callable later
             if let Ok(routine) = self.dictionary.get(&token) {
-                if routine.len() == 1 {
-                    for word in routine {
-                        match word {
-                            Cell::Compiler(ct_func) => {
-                                function.append(&mut ct_func(self)?);
-                                break;
-                            }
-                            _ => function.push(word),
-                        }
-                    }
-                } else {
-                    function.push(Cell::Call(token.clone()));
+                match &routine[..] {
+                    [Cell::Compiler(ct_func)] => function.append(&mut ct_func(self)?),
+                    [word] if *word != Cell::Recurse => function.push(word.clone()),
+                    _ => function.push(Cell::Call(token.as_str().into(), routine)),
                 }
             }
             // try to parse the input as a numeric value
             // this is not std conform we should read `BASE` variable that indicates
             // the radix (2-10-16)
-            else if let Ok(value) = std::primitive::i64::from_str_radix(&token, 10) {
-                function.push(Cell::Data(Variable::Int(value)).into());
+            else if let Ok(value) = token.parse::<i64>() {
+                function.push(Cell::Data(Variable::Int(value)));
             }
             // unknown token,
             // maybe an error or just a token we are not supposed to compile
@@ -159,56 +614,379 @@ impl VM {
         let _ = self.input_buffer.pop_front(); // pop the leading`:`
 
         if let Some(name) = self.input_buffer.pop_front() {
-            let function = self.compile()?;
+            let mut function = self.compile()?;
+            if self.optimize {
+                function = optimize(function);
+            }
+            if self.sandbox.denies(&name) {
+                return Err(Error::Denied(name));
+            }
+            if self.sandbox.limited() {
+                let replaced = self
+                    .dictionary
+                    .get_own(&name)
+                    .map_or(0, |code| Dictionary::cells_of(&code));
+                let cells = self.dictionary.cells() - replaced + Dictionary::cells_of(&function);
+                self.sandbox.check_literals(&function)?;
+                self.check_quotas(cells)?;
+            }
 
+            if self.dictionary.get(&name).is_ok() {
+                self.diagnose(Severity::Warning, &format!("redefined {}", name));
+            }
             self.dictionary.add(&name.to_lowercase(), function);
         }
         Ok(State::Interpret)
     }
 
     /// executes an entry from the dictionary
-    pub fn execute(&mut self, program: Vec<Cell>) -> Result<()> {
-        let mut pc = 0i64;
-        //println!("execute: {:?}", self);
-        //println!("execute: {:?}", program);
-        while pc < program.len() as i64 {
-            let word = program.get(pc as usize).ok_or(Error::Executor)?.clone();
-            //println!("pc({})word({:?})",pc,word);
-            //crate::builtins::dot_s(self);
-
-            let mut next_step = 1i64;
+    ///
+    /// calls don't recurse on the rust stack, every called definition
+    /// gets a `Frame` instead. more than `max_call_depth` frames end
+    /// execution with `Error::ReturnStackOverflow`.
+    ///
+    /// cells are executed in place, only literals are copied to the stack
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.handle_errors = false;
+    /// vm.max_call_depth = 100;
+    /// vm.tail_calls = false;
+    ///
+    /// vm.eval(": forever recurse 1 ;");
+    /// assert_eq!(vm.eval("forever"), Err(Error::ReturnStackOverflow));
+    /// assert!(vm.frames.is_empty());
+    ///
+    /// // the VM is still usable
+    /// vm.eval(": deep ?dup if 1- recurse then ;");
+    /// assert_eq!(vm.eval("99 deep"), Ok(()));
+    /// assert_eq!(vm.eval("100 deep"), Err(Error::ReturnStackOverflow));
+    ///
+    /// // the depth is only limited by memory, not by the rust stack
+    /// vm.max_call_depth = 1_000_000;
+    /// assert_eq!(vm.eval("500000 deep"), Ok(()));
+    /// ```
+    ///
+    /// calls in tail position reuse the frame of the caller,
+    /// unless `tail_calls` is switched off
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.handle_errors = false;
+    /// vm.max_call_depth = 100;
+    ///
+    /// vm.eval(": countdown dup . 1- ?dup if recurse then ;");
+    /// assert_eq!(vm.eval("1000 countdown"), Ok(()));
+    ///
+    /// vm.tail_calls = false;
+    /// assert_eq!(vm.eval("1000 countdown"), Err(Error::ReturnStackOverflow));
+    /// ```
+    pub fn execute(&mut self, program: Code) -> Result<()> {
+        self.execute_word("", program)
+    }
+
+    /// like `execute`, `name` is what `hooks` see as the running word
+    pub fn execute_word(&mut self, name: &str, program: Code) -> Result<()> {
+        let base = self.frames.len();
+        self.frames.push(Frame {
+            name: name.into(),
+            code: program,
+            pc: 0,
+        });
+        if self.profiler.enabled {
+            self.profiler.enter_word(name.into(), base + 1);
+        }
+        self.notify(0, |hooks, step| hooks.enter(step));
+        self.finish_frames(base)
+    }
+
+    /// runs the frames above `base` to the end
+    ///
+    /// on `Error::Yield` the frames are kept for `resume`
+    fn finish_frames(&mut self, base: usize) -> Result<()> {
+        let result = self.run_frames(base);
+        match &result {
+            Err(Error::Yield) => return result,
+            Err(error) => {
+                for depth in (base..self.frames.len()).rev() {
+                    let pc = self.frames[depth].pc.saturating_sub(1);
+                    self.notify_at(depth, pc, |hooks, step| hooks.error(step, error));
+                }
+                self.profiler.unwind(base);
+            }
+            Ok(()) => {}
+        }
+        self.frames.truncate(base);
+        if base == 0 {
+            // stepping ends with the word the interpreter started
+            self.debugger.mode = builtins::Mode::Run;
+        }
+        result
+    }
+
+    /// runs the inner interpreter until the frame at `base` returns
+    fn run_frames(&mut self, base: usize) -> Result<()> {
+        let tail_calls = self.tail_calls;
+        let limited = self.sandbox.limited();
+        while self.frames.len() > base {
+            let frame = self.frames.last_mut().ok_or(Error::Executor)?;
+            let pc = frame.pc;
+            if pc >= frame.code.len() {
+                // end of the definition
+                self.leave(base, pc);
+                continue;
+            }
+            if let Some(fuel) = self.fuel.as_mut() {
+                if *fuel == 0 {
+                    // only the words the interpreter started can be resumed,
+                    // the native word that started the others is gone
+                    return Err(match base {
+                        0 => Error::Yield,
+                        _ => Error::OutOfFuel,
+                    });
+                }
+                *fuel -= 1;
+            }
+            frame.pc += 1;
+            if self.hooks.is_some() {
+                self.notify(pc, |hooks, step| hooks.before(step));
+            }
+            if self.debugger.active() {
+                builtins::pause(self, pc)?;
+            }
+            let frame = self.frames.last().ok_or(Error::Executor)?;
+            let Some(word) = frame.code.get(pc) else {
+                return Err(Error::Executor);
+            };
+
+            let mut next_step = None;
             match word {
-                Cell::Exec(func) => func(self)?,
-                Cell::Compiled(func) => func(self)?,
-                Cell::Call(name) => self.execute(self.dictionary.get(&name)?)?,
-                Cell::Data(data) => self.value_stack.push(data),
+                Cell::Exec(func) | Cell::Compiled(func) => {
+                    let func = *func;
+                    if let Some(name) = self.sandbox.denied_native(func) {
+                        return Err(Error::Denied(name.to_owned()));
+                    }
+                    // a word of a single native cell is profiled as a word already
+                    if self.profiler.enabled && frame.code.len() > 1 {
+                        let depth = self.frames.len();
+                        self.profiler.enter_native(func, depth);
+                        let result = func(self);
+                        self.profiler.exit(depth);
+                        result?;
+                    } else {
+                        func(self)?;
+                    }
+                }
+                Cell::Closure(word) => {
+                    let word = word.clone();
+                    if self.profiler.enabled && frame.code.len() > 1 {
+                        let depth = self.frames.len();
+                        self.profiler.enter_closure(&word, depth);
+                        let result = word.call(self);
+                        self.profiler.exit(depth);
+                        result?;
+                    } else {
+                        word.call(self)?;
+                    }
+                }
+                Cell::Call(name, code) => {
+                    let (name, code) = (name.clone(), code.clone());
+                    let tail = tail_calls && is_tail(&frame.code, pc + 1);
+                    self.enter(name, code, tail)?;
+                    continue;
+                }
+                Cell::Recurse => {
+                    let (name, code) = (frame.name.clone(), frame.code.clone());
+                    let tail = tail_calls && is_tail(&frame.code, pc + 1);
+                    self.enter(name, code, tail)?;
+                    continue;
+                }
+                // otherwise the copy that follows runs
+                Cell::Inlined(name, code, step) if self.instrumented() => {
+                    let (name, code) = (name.clone(), code.clone());
+                    let tail =
+                        tail_calls && is_tail(&frame.code, pc.saturating_add(*step as usize));
+                    self.enter(name, code, tail)?;
+                    continue;
+                }
+                Cell::Inlined(..) => {}
+                Cell::Data(data) => {
+                    let data = data.clone();
+                    self.value_stack.push(data);
+                }
+                Cell::Print(text) => {
+                    let text = text.clone();
+                    self.write(&text)?;
+                }
                 Cell::ControlReturn => {
-                    return Ok(());
+                    self.leave(base, pc);
+                    continue;
+                }
+                Cell::ControlBranch(step) => next_step = Some(*step),
+                Cell::ControlBranchIfZero(step) => {
+                    let step = *step;
+                    if self.value_stack.pop()? == Variable::Int(0) {
+                        next_step = Some(step);
+                    }
+                }
+                Cell::ControlBranchIfNotZero(step) => {
+                    let step = *step;
+                    if self.value_stack.pop()? != Variable::Int(0) {
+                        next_step = Some(step);
+                    }
                 }
-                Cell::ControlBranch => {
-                    next_step = self.value_stack.pop()?.into();
+                Cell::ControlLoop(step) => {
+                    let step = *step;
+                    if self.loop_step(Variable::Int(1), false)? {
+                        next_step = Some(step);
+                    }
                 }
-                Cell::ControlBranchIfZero => {
-                    let branch_step: i64 = self.value_stack.pop()?.into();
-                    let branch_check = self.value_stack.pop()?;
-                    if branch_check == Variable::Int(0) {
-                        next_step = branch_step;
+                Cell::ControlPlusLoop(step) => {
+                    let step = *step;
+                    let increment = self.value_stack.pop()?;
+                    if self.loop_step(increment, false)? {
+                        next_step = Some(step);
                     }
                 }
-                Cell::ControlBranchIfNotZero => {
-                    let branch_step: i64 = self.value_stack.pop()?.into();
-                    let branch_check = self.value_stack.pop()?;
-                    if branch_check != Variable::Int(0) {
-                        next_step = branch_step;
+                Cell::ControlMinusLoop(step) => {
+                    let step = *step;
+                    let decrement = self.value_stack.pop()?;
+                    if self.loop_step(decrement, true)? {
+                        next_step = Some(step);
                     }
                 }
                 Cell::Compiler(_) => {
                     return Err(Error::Parser("Interpreting a compile-only word".to_owned()));
                 }
             };
-            //crate::builtins::dot_s(self);
-            pc += next_step;
+            if let Some(step) = next_step {
+                let frame = self.frames.last_mut().ok_or(Error::Executor)?;
+                frame.pc = usize::try_from(pc as i64 + step).map_err(|_| Error::Executor)?;
+            }
+            if limited {
+                self.check_quotas(self.dictionary.cells())?;
+            }
+            if self.hooks.is_some() {
+                self.notify(pc, |hooks, step| hooks.after(step));
+            }
+        }
+        Ok(())
+    }
+
+    /// returns from the innermost definition at `pc`
+    ///
+    /// the call in the caller is done now, unless the caller is below `base`
+    fn leave(&mut self, base: usize, pc: usize) {
+        self.notify(pc, |hooks, step| hooks.exit(step));
+        if self.profiler.enabled {
+            self.profiler.exit(self.frames.len());
+        }
+        self.frames.pop();
+        if self.frames.len() > base {
+            if let Some(caller) = self.frames.last_mut() {
+                let pc = caller.pc.saturating_sub(1);
+                // the copy of an inlined definition is done as well
+                if let Some(Cell::Inlined(_, _, step)) = caller.code.get(pc) {
+                    caller.pc = pc.saturating_add(*step as usize);
+                }
+                self.notify(pc, |hooks, step| hooks.after(step));
+            }
+        }
+    }
+
+    /// true if inlined definitions are called, so they are seen
+    /// by the debugger, the profiler and the hooks
+    fn instrumented(&self) -> bool {
+        self.debugger.active() || self.profiler.enabled || self.hooks.is_some()
+    }
+
+    /// calls `event` on the `hooks` with the innermost frame at `pc`
+    fn notify<F>(&mut self, pc: usize, event: F)
+    where
+        F: FnOnce(&mut dyn Hooks, &Step),
+    {
+        if let Some(depth) = self.frames.len().checked_sub(1) {
+            self.notify_at(depth, pc, event);
+        }
+    }
+
+    /// calls `event` on the `hooks` with the frame at `depth` at `pc`
+    fn notify_at<F>(&mut self, depth: usize, pc: usize, event: F)
+    where
+        F: FnOnce(&mut dyn Hooks, &Step),
+    {
+        let (Some(hooks), Some(frame)) = (self.hooks.as_deref_mut(), self.frames.get(depth)) else {
+            return;
+        };
+        let step = Step {
+            word: &frame.name,
+            code: &frame.code,
+            pc,
+            depth: depth + 1,
+            value_stack: &self.value_stack,
+            return_stack: &self.return_stack,
+            dictionary: &self.dictionary,
+        };
+        event(hooks, &step);
+    }
+
+    /// moves the index of the innermost `DO` loop on the return stack
+    ///
+    /// returns true if the loop goes on, otherwise index and limit are dropped
+    fn loop_step(&mut self, step: Variable, down: bool) -> Result<bool> {
+        let (Variable::Int(step), Variable::Int(index), Variable::Int(limit)) =
+            (step, self.return_stack.pop()?, self.return_stack.pop()?)
+        else {
+            return Err(Error::Type);
+        };
+        let (index, again) = if down {
+            let index = index.wrapping_sub(step);
+            (index, index > limit)
+        } else {
+            let index = index.wrapping_add(step);
+            (index, index < limit)
+        };
+        if again {
+            self.return_stack.push(limit);
+            self.return_stack.push(index);
         }
+        Ok(again)
+    }
+
+    /// enters a definition
+    ///
+    /// a tail call replaces the frame of the caller
+    fn enter(&mut self, name: Arc<str>, code: Code, tail: bool) -> Result<()> {
+        let frame = Frame { name, code, pc: 0 };
+        if tail {
+            let pc = self
+                .frames
+                .last()
+                .map_or(0, |caller| caller.pc.saturating_sub(1));
+            self.notify(pc, |hooks, step| hooks.exit(step));
+            if self.profiler.enabled {
+                self.profiler.exit(self.frames.len());
+            }
+            let caller = self.frames.last_mut().ok_or(Error::Executor)?;
+            *caller = frame;
+        } else {
+            if self.frames.len() >= self.max_call_depth {
+                return Err(Error::ReturnStackOverflow);
+            }
+            self.frames.push(frame);
+        }
+        if self.profiler.enabled {
+            let (name, depth) = (
+                self.frames[self.frames.len() - 1].name.clone(),
+                self.frames.len(),
+            );
+            self.profiler.enter_word(name, depth);
+        }
+        self.notify(0, |hooks, step| hooks.enter(step));
         Ok(())
     }
 
@@ -223,15 +1001,32 @@ impl VM {
 
         // interprete all input token by token
         while let Some(token) = self.input_buffer.pop_front() {
+            // a definition further down the line
+            if token == ":" {
+                self.input_buffer.push_front(token);
+                return self.state_fill_buffer();
+            }
+            if self.sandbox.denies(&token) {
+                return Err(Error::Denied(token));
+            }
             // is this token a word from the dictionary we execute it
             if let Ok(word) = self.dictionary.get(&token) {
-                self.execute(word)?;
+                // compiler words are compiled and the result is run right away
+                if let [Cell::Compiler(ct_func)] = &word[..] {
+                    let program = ct_func(self)?;
+                    self.execute_word(&token, program.into())?;
+                } else {
+                    self.execute_word(&token, word)?;
+                }
             }
             // try to parse the input as a numeric value
             // this is not std conform we should read `BASE` variable that indicates
             // the radix (2-10-16)
-            else if let Ok(value) = std::primitive::i64::from_str_radix(&token, 10) {
+            else if let Ok(value) = token.parse::<i64>() {
                 self.value_stack.push(Variable::Int(value));
+                if self.sandbox.limited() {
+                    self.check_quotas(self.dictionary.cells())?;
+                }
             }
             // we don't know how to handle this token
             else {
@@ -245,24 +1040,127 @@ impl VM {
     /// stays in fill buffer state until it sees a ';'
     fn state_fill_buffer(&mut self) -> Result<State> {
         if self.input_buffer.contains(&";".to_owned()) {
-            return Ok(State::Compile);
+            Ok(State::Compile)
         } else {
-            return Ok(State::FIllBuffer);
+            Ok(State::FIllBuffer)
         }
     }
 
-    /// prints error message and resets state machine if wanted
-    pub fn state_error(&self, error: Error) -> Result<State> {
-        (self.write)(&format!("Error: {}\n", error));
-        if self.handle_errors {
+    /// reports the error and resets state machine if wanted
+    ///
+    /// if errors are not handled here they are passed on
+    /// to the caller of `eval` without being reported
+    ///
+    /// `Error::Bye` and `Error::Yield` are never handled, they always end `eval`
+    pub fn state_error(&mut self, error: Error) -> Result<State> {
+        if self.handle_errors && !matches!(error, Error::Bye(_) | Error::Yield) {
+            self.diagnose(Severity::Error, &error.to_string());
             Ok(State::Interpret)
         } else {
             Err(error)
         }
     }
 
+    /// interpret a forth source file
+    ///
+    /// relative paths are resolved by `Files::resolve`.
+    /// the file is evaluated line by line and stops at the first error.
+    ///
+    /// ```
+    /// # use frust::*;
+    /// # let path = std::env::temp_dir().join("frust-doc-include.fr");
+    /// # std::fs::write(&path, ": answer\n  42 ;\n").unwrap();
+    /// let mut vm = VM::new_null();
+    ///
+    /// vm.include(&path.display().to_string()).unwrap();
+    /// vm.eval("answer");
+    /// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(42)));
+    ///
+    /// assert!(matches!(vm.include("/does/not/exist.fr"), Err(Error::Io(_))));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn include(&mut self, name: &str) -> Result<()> {
+        let path = self
+            .files
+            .resolve(name)
+            .ok_or(Error::Io(format!("{}: file not found", name)))?;
+        if self.files.including.contains(&path) {
+            return Err(Error::Io(format!("{}: recursive include", name)));
+        }
+        let source =
+            std::fs::read_to_string(&path).map_err(|error| Error::Io(error.to_string()))?;
+
+        self.files.included.insert(path.clone());
+        self.files.including.push(path.clone());
+        let result = self.include_source(&path.display().to_string(), &source);
+        self.files.including.pop();
+        result
+    }
+
+    /// like `include`, but does nothing if the file was included before
+    pub fn require(&mut self, name: &str) -> Result<()> {
+        match self.files.resolve(name) {
+            Some(path) if self.files.included.contains(&path) => Ok(()),
+            _ => self.include(name),
+        }
+    }
+
+    /// evaluate `source` line by line on its own input buffer
+    ///
+    /// errors are annotated with `name` and the line number
+    pub fn include_source(&mut self, name: &str, source: &str) -> Result<()> {
+        let input_buffer = mem::take(&mut self.input_buffer);
+        let state = mem::take(&mut self.state);
+        let handle_errors = mem::replace(&mut self.handle_errors, false);
+
+        let mut result = Ok(());
+        let mut line_number = 0;
+        for line in source.lines() {
+            line_number += 1;
+            match self.eval(line) {
+                Ok(()) => {}
+                Err(Error::Bye(code)) => {
+                    result = Err(Error::Bye(code));
+                    break;
+                }
+                Err(Error::Yield) => {
+                    // the file would go on without this line, it can't be resumed
+                    self.cancel();
+                    let error = Box::new(Error::OutOfFuel);
+                    result = Err(Error::Included(name.to_owned(), line_number, error));
+                    break;
+                }
+                Err(error) => {
+                    result = Err(Error::Included(
+                        name.to_owned(),
+                        line_number,
+                        Box::new(error),
+                    ));
+                    break;
+                }
+            }
+        }
+        if result.is_ok() && matches!(self.state, State::FIllBuffer) {
+            let error = Error::Parser("unterminated definition".to_owned());
+            result = Err(Error::Included(
+                name.to_owned(),
+                line_number,
+                Box::new(error),
+            ));
+        }
+
+        self.input_buffer = input_buffer;
+        self.state = state;
+        self.handle_errors = handle_errors;
+        result
+    }
+
     /// Takes an input and evaluates it.
     /// automatically switch between interpreter and compiler
+    ///
+    /// `eval` never panics, whatever the input. integer arithmetic wraps
+    /// around and everything else that goes wrong is an `Error`,
+    /// see `tests/fuzz.rs`.
     /// ```
     /// # use frust::*;
     /// # let mut vm = VM::new_null();
@@ -271,9 +1169,90 @@ impl VM {
     /// vm.eval("5 4 + . ");
     /// ```
     pub fn eval(&mut self, input: &str) -> Result<()> {
-        self.input_buffer
-            .extend(input.split_whitespace().map(|token| token.to_owned()));
+        // `\` comments end with their line, not with the input buffer
+        for line in input.lines() {
+            self.input_buffer.extend(
+                line.split_whitespace()
+                    .take_while(|token| *token != "\\")
+                    .map(|token| token.to_owned()),
+            );
+        }
+
+        let result = self.run();
+        let flushed = self.flush();
+        result.and(flushed)
+    }
+
+    /// goes on where `eval` stopped with `Error::Yield`
+    ///
+    /// refill `fuel` first, `resume` yields again when it runs out.
+    /// the rest of the input of `eval` is evaluated as well.
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let output = Capture::new();
+    /// let mut vm = VM::new(std::io::empty(), output.clone());
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.fuel = Some(100);
+    ///
+    /// let mut result = vm.eval(": count 0 do i . loop ; 1000 count 42 .");
+    /// let mut slices = 1;
+    /// while result == Err(Error::Yield) {
+    ///     assert!(vm.suspended());
+    ///     vm.fuel = Some(100);
+    ///     result = vm.resume();
+    ///     slices += 1;
+    /// }
+    ///
+    /// assert_eq!(result, Ok(()));
+    /// assert!(slices > 10);
+    /// assert!(output.take().ends_with(" 998 999 42 "));
+    /// ```
+    pub fn resume(&mut self) -> Result<()> {
+        let result = match self.frames.is_empty() {
+            true => Ok(()),
+            false => self.finish_frames(0),
+        };
+        let result = match result {
+            Err(error) => self.state_error(error).map(|state| self.state = state),
+            Ok(()) => Ok(()),
+        };
+        let result = result.and_then(|()| self.run());
+        let flushed = self.flush();
+        result.and(flushed)
+    }
+
+    /// true if `eval` stopped with `Error::Yield` and can be resumed
+    pub fn suspended(&self) -> bool {
+        !self.frames.is_empty()
+    }
 
+    /// drops the execution stopped by `Error::Yield` and the rest of its input
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.fuel = Some(1000);
+    ///
+    /// assert_eq!(vm.eval(": forever recurse ; forever 1 2 3"), Err(Error::Yield));
+    /// vm.cancel();
+    ///
+    /// assert!(!vm.suspended());
+    /// vm.fuel = Some(1000);
+    /// assert_eq!(vm.eval("4"), Ok(()));
+    /// assert_eq!(vm.value_stack.len(), 1);
+    /// ```
+    pub fn cancel(&mut self) {
+        self.profiler.unwind(0);
+        self.frames.clear();
+        self.input_buffer.clear();
+        self.state = State::Interpret;
+        self.debugger.mode = builtins::Mode::Run;
+    }
+
+    /// runs the state machine until the input buffer is consumed
+    fn run(&mut self) -> Result<()> {
         while !self.input_buffer.is_empty() || !self.state.is_idling() {
             let new_state = match mem::take(&mut self.state) {
                 State::Taken => Ok(State::Interpret),
@@ -282,6 +1261,11 @@ impl VM {
                 State::Compile => self.state_compile(),
             };
             match new_state {
+                // the definition is not complete, wait for the next input
+                Ok(State::FIllBuffer) => {
+                    self.state = State::FIllBuffer;
+                    break;
+                }
                 Ok(state) => self.state = state,
                 Err(error) => self.state = self.state_error(error)?,
             }
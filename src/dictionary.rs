@@ -1,8 +1,9 @@
 use crate::{Error, Result, VM, Variable};
 use std::{
+    cell::RefCell,
     collections::HashMap,
     fmt::{Debug, Display},
-    os::raw::c_void,
+    sync::{Arc, Mutex},
 };
 
 /// interface for rust `word-functions`
@@ -19,6 +20,96 @@ use std::{
 ///
 type WordFunction = fn(&mut VM) -> Result<()>;
 type CompileFunction = fn(&mut VM) -> Result<Vec<Cell>>;
+type ClosureFunction = dyn FnMut(&mut VM) -> Result<()> + Send;
+
+/// a native word that captures state, see `Dictionary::add_closure`
+///
+/// copies share the same closure, they are equal if they share it
+///
+/// ```
+/// # use frust::*;
+/// let word = Closure::new(|_| Ok(()));
+/// let other = Closure::new(|_| Ok(()));
+/// assert_eq!(Cell::Closure(word.clone()), Cell::Closure(word.clone()));
+/// assert_ne!(Cell::Closure(word.clone()), Cell::Closure(other));
+/// assert_eq!(format!("{:?}", Cell::Closure(word)), "Closure");
+/// ```
+#[derive(Clone)]
+pub struct Closure(Arc<Mutex<ClosureFunction>>);
+
+impl Closure {
+    pub fn new<F>(word: F) -> Closure
+    where
+        F: FnMut(&mut VM) -> Result<()> + Send + 'static,
+    {
+        Closure(Arc::new(Mutex::new(word)))
+    }
+
+    /// runs the closure
+    ///
+    /// calls from other threads wait for each other. a closure that calls
+    /// itself, through `VM::eval` for example, fails with `Error::Executor`
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.handle_errors = false;
+    /// vm.dictionary.add_closure("inner", |vm| vm.eval("1 2 +"));
+    /// vm.dictionary.add_closure("again", |vm| vm.eval("again"));
+    ///
+    /// vm.eval("inner").unwrap();
+    /// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(3)));
+    /// assert_eq!(vm.eval("again"), Err(Error::Executor));
+    /// ```
+    pub fn call(&self, vm: &mut VM) -> Result<()> {
+        let _running = Running::enter(self.id()).ok_or(Error::Executor)?;
+        // a panic in an earlier call leaves the closure as it is
+        let mut word = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        word(vm)
+    }
+
+    /// the same for all copies of the closure
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+}
+
+thread_local! {
+    /// ids of the closures running on this thread
+    static RUNNING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// marks a closure as running on this thread until it is dropped
+struct Running(usize);
+
+impl Running {
+    /// `None` if the closure is already running on this thread
+    fn enter(id: usize) -> Option<Running> {
+        RUNNING.with_borrow_mut(|running| {
+            if running.contains(&id) {
+                return None;
+            }
+            running.push(id);
+            Some(Running(id))
+        })
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.with_borrow_mut(|running| running.retain(|id| *id != self.0));
+    }
+}
+
+/// body of a dictionary entry
+///
+/// shared between the dictionary and every definition that calls it,
+/// so looking up or calling a word never copies its cells
+pub type Code = Arc<[Cell]>;
 
 /// `Cell` represents **named** forth executable ***Cell***
 ///
@@ -27,17 +118,91 @@ type CompileFunction = fn(&mut VM) -> Result<Vec<Cell>>;
 /// - `Native`: rust Cell that will operate on the forth context and input-buffer
 /// - `Dynamic`: forth Cell written in forth and *compiled*.
 ///
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Clone)]
 pub enum Cell {
     Exec(WordFunction),
     Compiler(CompileFunction),
     Compiled(WordFunction),
+    /// native word with captured state
+    Closure(Closure),
     Data(Variable),
-    Call(String),
+    /// call of a definition, bound when the caller is compiled
+    Call(Arc<str>, Code),
+    /// call of the definition that contains this cell
+    Recurse,
+    /// a copy of the body of the definition follows, put in place of its
+    /// `Call` by the optimizer. the offset goes past the copy. the VM calls
+    /// the definition instead while it is debugged, profiled or hooked
+    Inlined(Arc<str>, Code, i64),
+    /// print the string, compiled by `."`
+    Print(Arc<str>),
     ControlReturn,
-    ControlBranch,
-    ControlBranchIfZero,
-    ControlBranchIfNotZero,
+    /// jump by the offset, relative to this cell
+    ControlBranch(i64),
+    /// pop a flag and jump by the offset if it is zero
+    ControlBranchIfZero(i64),
+    /// pop a flag and jump by the offset if it is not zero
+    ControlBranchIfNotZero(i64),
+    /// end of `DO ... LOOP`, jumps back by the offset while the loop goes on
+    ControlLoop(i64),
+    /// end of `DO ... +LOOP`
+    ControlPlusLoop(i64),
+    /// end of `DO ... -LOOP`
+    ControlMinusLoop(i64),
+}
+impl PartialEq for Cell {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Cell::Exec(a), Cell::Exec(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Cell::Compiler(a), Cell::Compiler(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Cell::Compiled(a), Cell::Compiled(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Cell::Closure(a), Cell::Closure(b)) => a.id() == b.id(),
+            (Cell::Data(a), Cell::Data(b)) => a == b,
+            (Cell::Call(a, a_code), Cell::Call(b, b_code)) => a == b && a_code == b_code,
+            (Cell::Recurse, Cell::Recurse) => true,
+            (Cell::Inlined(a, a_code, a_step), Cell::Inlined(b, b_code, b_step)) => {
+                a == b && a_code == b_code && a_step == b_step
+            }
+            (Cell::Print(a), Cell::Print(b)) => a == b,
+            (Cell::ControlReturn, Cell::ControlReturn) => true,
+            (Cell::ControlBranch(a), Cell::ControlBranch(b)) => a == b,
+            (Cell::ControlBranchIfZero(a), Cell::ControlBranchIfZero(b)) => a == b,
+            (Cell::ControlBranchIfNotZero(a), Cell::ControlBranchIfNotZero(b)) => a == b,
+            (Cell::ControlLoop(a), Cell::ControlLoop(b)) => a == b,
+            (Cell::ControlPlusLoop(a), Cell::ControlPlusLoop(b)) => a == b,
+            (Cell::ControlMinusLoop(a), Cell::ControlMinusLoop(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+/// function pointers and closures differ from run to run, so only their kind is shown
+impl Debug for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cell::Exec(_) => write!(f, "Exec"),
+            Cell::Compiler(_) => write!(f, "Compiler"),
+            Cell::Compiled(_) => write!(f, "Compiled"),
+            Cell::Closure(_) => write!(f, "Closure"),
+            Cell::Data(value) => f.debug_tuple("Data").field(value).finish(),
+            Cell::Call(name, _) => f.debug_tuple("Call").field(name).finish(),
+            Cell::Recurse => write!(f, "Recurse"),
+            Cell::Inlined(name, _, step) => {
+                f.debug_tuple("Inlined").field(name).field(step).finish()
+            }
+            Cell::Print(text) => f.debug_tuple("Print").field(text).finish(),
+            Cell::ControlReturn => write!(f, "ControlReturn"),
+            Cell::ControlBranch(step) => f.debug_tuple("ControlBranch").field(step).finish(),
+            Cell::ControlBranchIfZero(step) => {
+                f.debug_tuple("ControlBranchIfZero").field(step).finish()
+            }
+            Cell::ControlBranchIfNotZero(step) => {
+                f.debug_tuple("ControlBranchIfNotZero").field(step).finish()
+            }
+            Cell::ControlLoop(step) => f.debug_tuple("ControlLoop").field(step).finish(),
+            Cell::ControlPlusLoop(step) => f.debug_tuple("ControlPlusLoop").field(step).finish(),
+            Cell::ControlMinusLoop(step) => f.debug_tuple("ControlMinusLoop").field(step).finish(),
+        }
+    }
 }
 impl Display for Cell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -60,9 +225,17 @@ impl From<CompileFunction> for Cell {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// words by name
+///
+/// a dictionary may sit on a shared `base`, see `with_base`. words are
+/// looked up in its own entries first, then in the base.
+#[derive(Debug, PartialEq, Default)]
 pub struct Dictionary {
-    data: HashMap<String, Vec<Cell>>,
+    data: HashMap<String, Code>,
+    /// cells of the own entries
+    cells: usize,
+    /// read only words below the own ones
+    base: Option<Arc<Dictionary>>,
 }
 
 impl Dictionary {
@@ -75,17 +248,213 @@ impl Dictionary {
     pub fn new() -> Dictionary {
         Dictionary {
             data: HashMap::new(),
+            cells: 0,
+            base: None,
         }
     }
 
+    /// create an empty dictionary on top of `base`
+    ///
+    /// the base is shared, not copied: many VMs can start from the same
+    /// library for the cost of an `Arc`. new definitions go to the own
+    /// entries and may shadow words of the base, the base never changes.
+    ///
+    /// ```
+    /// # use frust::*;
+    /// # use std::sync::Arc;
+    /// let mut vm = VM::new_null();
+    /// builtins::add_core(&mut vm.dictionary);
+    /// vm.eval(": sq dup * ;").unwrap();
+    /// let base = Arc::new(std::mem::take(&mut vm.dictionary));
+    ///
+    /// let mut vm = VM::new_null();
+    /// vm.dictionary = Dictionary::with_base(base.clone());
+    /// vm.eval(": sq drop 0 ; 3 sq").unwrap();
+    /// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(0)));
+    ///
+    /// let mut vm = VM::new_null();
+    /// vm.dictionary = Dictionary::with_base(base.clone());
+    /// vm.eval("3 sq").unwrap();
+    /// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(9)));
+    /// assert_eq!(vm.dictionary.cells(), 0);
+    /// // both VMs run the body of the base
+    /// assert!(Arc::ptr_eq(&vm.dictionary.get("sq").unwrap(), &base.get("sq").unwrap()));
+    /// assert_eq!(Arc::strong_count(&base), 3);
+    /// ```
+    pub fn with_base(base: Arc<Dictionary>) -> Dictionary {
+        Dictionary {
+            base: Some(base),
+            ..Dictionary::new()
+        }
+    }
+
+    /// the shared words below the own ones
+    pub fn base(&self) -> Option<&Arc<Dictionary>> {
+        self.base.as_ref()
+    }
+
     pub fn add<T>(&mut self, name: &str, dict_value: T)
     where
         T: Into<Vec<Cell>>,
     {
-        self.data.insert(name.to_string(), dict_value.into());
+        self.add_code(name, dict_value.into().into());
     }
 
-    pub fn get(&self, name: &str) -> Result<Vec<Cell>> {
+    /// like `add`, but shares `code` with its callers
+    pub fn add_code(&mut self, name: &str, code: Code) {
+        self.cells += Dictionary::cells_of(&code);
+        if let Some(old) = self.data.insert(name.to_string(), code) {
+            self.cells -= Dictionary::cells_of(&old);
+        }
+    }
+
+    /// add a native word that captures state
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut vm = VM::new_null();
+    /// let mut count = 0;
+    /// vm.dictionary.add_closure("count", move |vm| {
+    ///     count += 1;
+    ///     vm.value_stack.push(count);
+    ///     Ok(())
+    /// });
+    ///
+    /// vm.eval("count count count").unwrap();
+    /// assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(3)));
+    /// ```
+    pub fn add_closure<F>(&mut self, name: &str, word: F)
+    where
+        F: FnMut(&mut VM) -> Result<()> + Send + 'static,
+    {
+        self.add(name, Cell::Closure(Closure::new(word)));
+    }
+
+    /// number of cells of the own words, a native word is one
+    ///
+    /// words of the base are not counted, they are shared.
+    /// literals count the cells their bytes fill, see `cells_of`
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut dict = Dictionary::new();
+    /// dict.add("dup", Cell::Exec(builtins::dup));
+    /// dict.add("2dup", vec![Cell::Exec(builtins::over), Cell::Exec(builtins::over)]);
+    /// assert_eq!(dict.cells(), 3);
+    ///
+    /// dict.add("2dup", Cell::Exec(builtins::two_dup));
+    /// assert_eq!(dict.cells(), 2);
+    /// ```
+    pub fn cells(&self) -> usize {
+        self.cells
+    }
+
+    /// number of cells `code` takes in a dictionary
+    ///
+    /// strings and arrays compiled into it count one cell for every
+    /// `size_of::<Cell>()` bytes they hold, on top of their own
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let text = Cell::Data(Variable::from(&*"x".repeat(100)));
+    /// assert_eq!(Dictionary::cells_of(&[Cell::Exec(builtins::dup)]), 1);
+    /// assert!(Dictionary::cells_of(&[text]) > 100 / std::mem::size_of::<Cell>());
+    /// ```
+    pub fn cells_of(code: &[Cell]) -> usize {
+        let bytes = |cell: &Cell| match cell {
+            Cell::Data(value) => value.heap_size(),
+            Cell::Print(text) => text.len(),
+            _ => 0,
+        };
+        code.iter()
+            .map(|cell| 1 + bytes(cell).div_ceil(std::mem::size_of::<Cell>()))
+            .sum()
+    }
+
+    /// all words that can be looked up, the own ones and those of the
+    /// base they don't shadow
+    fn entries(&self) -> Box<dyn Iterator<Item = (&str, &Code)> + '_> {
+        let own = self.data.iter().map(|(name, code)| (name.as_str(), code));
+        match &self.base {
+            Some(base) => Box::new(
+                own.chain(
+                    base.entries()
+                        .filter(|(name, _)| !self.data.contains_key(*name)),
+                ),
+            ),
+            None => Box::new(own),
+        }
+    }
+
+    /// names of all words in the dictionary, including the base
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut dict = Dictionary::new();
+    /// dict.add("dup", Cell::Exec(builtins::dup));
+    ///
+    /// assert_eq!(dict.names().collect::<Vec<_>>(), vec!["dup"]);
+    ///
+    /// // a shadowed word of the base is named once
+    /// let mut dict = Dictionary::with_base(std::sync::Arc::new(dict));
+    /// dict.add("dup", Cell::Exec(builtins::over));
+    /// assert_eq!(dict.names().collect::<Vec<_>>(), vec!["dup"]);
+    /// ```
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries().map(|(name, _)| name)
+    }
+
+    /// name of the word that is just `cell`, the first one if there are several
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut dict = Dictionary::new();
+    /// dict.add("dup", Cell::Exec(builtins::dup));
+    ///
+    /// assert_eq!(dict.name_of(&Cell::Exec(builtins::dup)), Some("dup"));
+    /// assert_eq!(dict.name_of(&Cell::Exec(builtins::drop)), None);
+    /// ```
+    pub fn name_of(&self, cell: &Cell) -> Option<&str> {
+        self.entries()
+            .filter(|(_, code)| code[..] == [cell.clone()])
+            .map(|(name, _)| name)
+            .min()
+    }
+
+    /// body of the word `name`, ignoring case
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut dict = Dictionary::new();
+    /// dict.add("dup", Cell::Exec(builtins::dup));
+    ///
+    /// let code = dict.get("DUP").unwrap();
+    /// assert_eq!(code[..], [Cell::Exec(builtins::dup)]);
+    /// // both share the same body
+    /// assert!(std::sync::Arc::ptr_eq(&code, &dict.get("dup").unwrap()));
+    /// ```
+    pub fn get(&self, name: &str) -> Result<Code> {
+        match (self.get_own(name), &self.base) {
+            (Err(_), Some(base)) => base.get(name),
+            (code, _) => code,
+        }
+    }
+
+    /// like `get`, but without looking into the base
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut base = Dictionary::new();
+    /// base.add("dup", Cell::Exec(builtins::dup));
+    /// base.add("over", Cell::Exec(builtins::over));
+    /// let mut dict = Dictionary::with_base(std::sync::Arc::new(base));
+    /// dict.add("dup", Cell::Exec(builtins::over));
+    ///
+    /// assert_eq!(dict.get_own("dup").unwrap()[..], [Cell::Exec(builtins::over)]);
+    /// assert!(dict.get_own("over").is_err());
+    /// assert!(dict.get("over").is_ok());
+    /// ```
+    pub fn get_own(&self, name: &str) -> Result<Code> {
         self.data
             .get(&name.to_lowercase())
             .cloned()
@@ -13,7 +13,40 @@ pub enum Error {
     Parser(String),
     Stack,
     Type,
+    /// `VM::call` expected this many results, the word left that many
+    Arity(usize, usize),
+    /// `/` or `mod` with a divisor of `0`
+    DivisionByZero,
     Unimplemented(String),
+    Io(String),
+    /// more nested calls than `VM::max_call_depth` allows
+    ReturnStackOverflow,
+    /// more values on the return stack than `Sandbox::max_return_stack`
+    ReturnStackFull,
+    /// more values on the value stack than `Sandbox::max_value_stack`
+    ValueStackOverflow,
+    /// a definition would grow the dictionary beyond `Sandbox::max_dictionary`
+    DictionaryFull,
+    /// a string longer than `Sandbox::max_string`
+    StringTooLong,
+    /// an array longer than `Sandbox::max_array`
+    ArrayTooLong,
+    /// more memory in use than `Sandbox::max_memory`
+    OutOfMemory,
+    /// the word is denied by the `Sandbox`
+    Denied(String),
+    /// `VM::fuel` ran out, `VM::resume` goes on where execution stopped
+    Yield,
+    /// `VM::fuel` ran out where execution can't be suspended,
+    /// inside an included file or a word run by a native word
+    OutOfFuel,
+    /// an image that can't be loaded, see `image`
+    Image(String),
+    /// an image uses a native word of this name that isn't known
+    MissingNative(String),
+    Included(String, usize, Box<Error>),
+    /// the program asked to leave forth with this exit code
+    Bye(i64),
     Prev(Vec<Error>)
 }
 pub type Result<T> = std::result::Result<T, Error>;
@@ -30,8 +63,42 @@ impl Debug for Error {
             Error::Parser(token) => write!(f, "Parsing failed ({:?})", token),
             Error::Stack => write!(f, "Stack"),
             Error::Type => write!(f, "Type"),
+            Error::Arity(expected, found) => {
+                write!(f, "Arity(expected {}, found {})", expected, found)
+            }
+            Error::DivisionByZero => write!(f, "DivisionByZero"),
             Error::Unimplemented(name) => write!(f, "Unimplemented({:?})",name),
+            Error::Io(message) => write!(f, "Io({})", message),
+            Error::ReturnStackOverflow => write!(f, "ReturnStackOverflow"),
+            Error::ReturnStackFull => write!(f, "ReturnStackFull"),
+            Error::ValueStackOverflow => write!(f, "ValueStackOverflow"),
+            Error::DictionaryFull => write!(f, "DictionaryFull"),
+            Error::StringTooLong => write!(f, "StringTooLong"),
+            Error::ArrayTooLong => write!(f, "ArrayTooLong"),
+            Error::OutOfMemory => write!(f, "OutOfMemory"),
+            Error::Denied(name) => write!(f, "Denied({:?})", name),
+            Error::Yield => write!(f, "Yield"),
+            Error::OutOfFuel => write!(f, "OutOfFuel"),
+            Error::Image(message) => write!(f, "Image({})", message),
+            Error::MissingNative(name) => write!(f, "MissingNative({:?})", name),
+            Error::Included(file, line, error) => write!(f, "{}:{}: {:?}", file, line, error),
+            Error::Bye(code) => write!(f, "Bye({})", code),
             Error::Prev(other) => write!(f,"[{:?}]",other),
         }
     }
-}
\ No newline at end of file
+}
+
+/// how serious a diagnostic message is
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Error => write!(f, "Error"),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{Cell, Code, Dictionary, Error, Stack};
+
+/// where the inner interpreter is, handed to `Hooks`
+pub struct Step<'a> {
+    /// name of the running word, empty for anonymous programs
+    pub word: &'a str,
+    /// the running definition
+    pub code: &'a Code,
+    /// index of the cell in `code`
+    pub pc: usize,
+    /// number of frames, `1` for the word the interpreter started
+    pub depth: usize,
+    pub value_stack: &'a Stack,
+    pub return_stack: &'a Stack,
+    pub dictionary: &'a Dictionary,
+}
+
+impl Step<'_> {
+    /// the cell at `pc`, `None` at the end of the definition
+    pub fn cell(&self) -> Option<&Cell> {
+        self.code.get(self.pc)
+    }
+}
+
+/// callbacks of the inner interpreter, install them in `VM::hooks`
+///
+/// hooks have to be `Send`, wrap them in a `Local` otherwise
+///
+/// all methods do nothing by default.
+///
+/// - `enter` when a word starts, `pc` is `0`
+/// - `before` and `after` every cell, `after` a call comes when it returned
+/// - `exit` when a word returns, on `;`, `EXIT` or before a tail call
+///   replaces it. a call in tail position gets no `after` then.
+/// - `error` when a cell fails, for every word that is left because of it.
+///   there is no `exit` for these words.
+///
+/// words the optimizer inlined are called while hooks are installed,
+/// their `Inlined` cell is the call and the copy after it is skipped.
+///
+/// ```
+/// # use frust::*;
+/// # use std::sync::{Arc, Mutex};
+/// #[derive(Default)]
+/// struct Trace(Arc<Mutex<Vec<String>>>);
+///
+/// impl Hooks for Trace {
+///     fn enter(&mut self, step: &Step) {
+///         self.0.lock().unwrap().push(format!("enter {}", step.word));
+///     }
+///     fn before(&mut self, step: &Step) {
+///         let cell = step.cell().map(|cell| cell.to_string()).unwrap_or_default();
+///         let top = step.value_stack.at(0).map(|top| top.to_string()).unwrap_or_default();
+///         self.0.lock().unwrap().push(format!("{}:{} {} [{}]", step.word, step.pc, cell, top));
+///     }
+///     fn exit(&mut self, step: &Step) {
+///         self.0.lock().unwrap().push(format!("exit {}", step.word));
+///     }
+///     fn error(&mut self, step: &Step, error: &Error) {
+///         self.0.lock().unwrap().push(format!("{}:{} {}", step.word, step.pc, error));
+///     }
+/// }
+///
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// vm.eval(": sq dup * ; : f 2 sq drop ;");
+///
+/// let trace = Trace::default();
+/// let lines = trace.0.clone();
+/// vm.hooks = Some(Box::new(trace));
+/// vm.eval("f drop");
+///
+/// assert_eq!(
+///     *lines.lock().unwrap(),
+///     [
+///         "enter f",
+///         "f:0 Data(Int(2)) []",
+///         "f:1 Inlined(\"sq\", 3) [2]",
+///         "enter sq",
+///         "sq:0 Exec [2]",
+///         "sq:1 Exec [2]",
+///         "exit sq",
+///         "f:4 Exec [4]",
+///         "exit f",
+///         "enter drop",
+///         "drop:0 Exec []",
+///         "drop:0 Stack",
+///     ]
+/// );
+/// ```
+pub trait Hooks {
+    fn enter(&mut self, _step: &Step) {}
+    fn before(&mut self, _step: &Step) {}
+    fn after(&mut self, _step: &Step) {}
+    fn exit(&mut self, _step: &Step) {}
+    fn error(&mut self, _step: &Step, _error: &Error) {}
+}
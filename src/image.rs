@@ -0,0 +1,574 @@
+//! images of a VM, to start with a loaded library or to keep a session
+//!
+//! an image holds the dictionary, both stacks and the settings
+//! `tail_calls`, `optimize` and `max_call_depth`. all numbers are little
+//! endian, counts and lengths are `u32`, strings are a length and utf-8.
+//!
+//! ```text
+//! magic      b"FRUSTIMG"
+//! version    u32, `IMAGE_VERSION`
+//! settings   tail_calls u8, optimize u8, max_call_depth u64
+//! natives    count, names of the native words used by the cells
+//! bodies     count, each a count of cells, a body only calls earlier ones
+//! dictionary count, each a name and the index of its body
+//! stacks     count and values of the value stack, then the return stack,
+//!            bottom first
+//! ```
+//!
+//! a cell is a tag byte and its operand
+//!
+//! ```text
+//! 0 Exec, 1 Compiled, 2 Compiler, 3 Closure   u32 index into natives
+//! 4 Data                                      value
+//! 5 Call                                      name, u32 index into bodies
+//! 6 Recurse, 8 ControlReturn                  -
+//! 7 Print                                     string
+//! 9 ControlBranch, 10 ControlBranchIfZero,
+//! 11 ControlBranchIfNotZero, 12 ControlLoop,
+//! 13 ControlPlusLoop, 14 ControlMinusLoop     i64 offset
+//! 15 Inlined                                  name, u32 index into bodies,
+//!                                             i64 offset
+//! ```
+//!
+//! a value is a tag byte, `0` and an `i64`, `1` and a string
+//! or `2` and a count of values.
+//!
+//! native words are stored by name and looked up again when the image is
+//! loaded, in the word sets of `builtins` and in the dictionary of the VM
+//! that loads it. register closures and words of the host before loading.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use crate::{
+    builtins::{runtime_do, WordSet, SUPERINSTRUCTIONS},
+    Cell, Code, Dictionary, Error, Result, Stack, Variable, VM,
+};
+
+/// first bytes of every image
+pub const IMAGE_MAGIC: &[u8; 8] = b"FRUSTIMG";
+/// version of the format, images of other versions are not loaded
+pub const IMAGE_VERSION: u32 = 1;
+
+/// values nested deeper than this are a broken image
+const MAX_NESTING: usize = 256;
+
+/// identity of a native cell, its function or `Closure::id`
+fn native_key(cell: &Cell) -> Option<usize> {
+    match cell {
+        Cell::Exec(word) | Cell::Compiled(word) => Some(*word as *const () as usize),
+        Cell::Compiler(word) => Some(*word as *const () as usize),
+        Cell::Closure(word) => Some(word.id()),
+        _ => None,
+    }
+}
+
+/// native words by name, from all word sets and `dictionary`
+///
+/// `(do)` is compiled by `DO` and not a word of its own
+fn natives(dictionary: &Dictionary) -> HashMap<String, Cell> {
+    let mut natives = HashMap::new();
+    natives.insert("(do)".to_owned(), Cell::Exec(runtime_do));
+    for (name, word) in SUPERINSTRUCTIONS {
+        natives.insert(format!("({})", name), Cell::Exec(word));
+    }
+    let sets = [
+        WordSet::Core,
+        WordSet::Facility,
+        WordSet::Tools,
+        WordSet::File,
+        WordSet::Tester,
+    ];
+    let mut words = Dictionary::new();
+    for set in sets {
+        set.add(&mut words);
+    }
+    for words in [&words, dictionary] {
+        for name in words.names() {
+            if let Ok(code) = words.get(name) {
+                if let [cell] = &code[..] {
+                    if native_key(cell).is_some() {
+                        natives.insert(name.to_owned(), cell.clone());
+                    }
+                }
+            }
+        }
+    }
+    natives
+}
+
+fn count(len: usize) -> Result<u32> {
+    u32::try_from(len).map_err(|_| Error::Image("too big for an image".to_owned()))
+}
+
+/// builds an image in memory
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    fn i64(&mut self, value: i64) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    fn count(&mut self, len: usize) -> Result<()> {
+        self.u32(count(len)?);
+        Ok(())
+    }
+
+    fn string(&mut self, text: &str) -> Result<()> {
+        self.count(text.len())?;
+        self.bytes.extend(text.as_bytes());
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Variable) -> Result<()> {
+        match value {
+            Variable::Int(value) => {
+                self.u8(0);
+                self.i64(*value);
+            }
+            Variable::String(text) => {
+                self.u8(1);
+                self.string(text)?;
+            }
+            Variable::Array(values) => {
+                self.u8(2);
+                self.count(values.len())?;
+                for value in values {
+                    self.value(value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn stack(&mut self, stack: &Stack) -> Result<()> {
+        self.count(stack.len())?;
+        let values: Vec<&Variable> = stack.iter().collect();
+        for value in values.into_iter().rev() {
+            self.value(value)?;
+        }
+        Ok(())
+    }
+}
+
+/// the bodies of all words, callees before their callers
+#[derive(Default)]
+struct Bodies {
+    bodies: Vec<Code>,
+    index: HashMap<usize, usize>,
+}
+
+fn body_key(code: &Code) -> usize {
+    Arc::as_ptr(code) as *const () as usize
+}
+
+impl Bodies {
+    /// adds `root` and everything it calls, without recursion
+    fn collect(&mut self, root: &Code) {
+        let mut todo = vec![(root.clone(), false)];
+        while let Some((code, called)) = todo.pop() {
+            if self.index.contains_key(&body_key(&code)) {
+                continue;
+            }
+            if called {
+                self.index.insert(body_key(&code), self.bodies.len());
+                self.bodies.push(code);
+                continue;
+            }
+            todo.push((code.clone(), true));
+            for cell in code.iter() {
+                if let Cell::Call(_, callee) | Cell::Inlined(_, callee, _) = cell {
+                    todo.push((callee.clone(), false));
+                }
+            }
+        }
+    }
+}
+
+/// writes an image of `vm` to `output`, see the module documentation
+///
+/// `Error::Image` if a native word has no name
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// vm.eval(": sq dup * ; 7").unwrap();
+/// let mut image = Vec::new();
+/// save_image(&vm, &mut image).unwrap();
+///
+/// let mut loaded = VM::new_null();
+/// load_image(&mut loaded, &mut &image[..]).unwrap();
+/// loaded.eval("sq").unwrap();
+/// assert_eq!(loaded.value_stack.pop(), Ok(Variable::Int(49)));
+/// ```
+pub fn save_image(vm: &VM, output: &mut dyn Write) -> Result<()> {
+    let mut names: HashMap<usize, String> = HashMap::new();
+    for (name, cell) in natives(&vm.dictionary) {
+        if let Some(key) = native_key(&cell) {
+            let name = names
+                .get(&key)
+                .map_or(name.clone(), |known| known.clone().min(name));
+            names.insert(key, name);
+        }
+    }
+
+    // in order of the names, the same dictionary gives the same image
+    let mut words: Vec<(&str, Code)> = Vec::new();
+    let mut names_in_order: Vec<&str> = vm.dictionary.names().collect();
+    names_in_order.sort();
+    let mut bodies = Bodies::default();
+    for name in names_in_order {
+        let code = vm.dictionary.get(name)?;
+        bodies.collect(&code);
+        words.push((name, code));
+    }
+
+    // natives are numbered as they turn up in the bodies
+    let mut used: Vec<String> = Vec::new();
+    let mut used_index: HashMap<usize, u32> = HashMap::new();
+    let mut cells = Writer::default();
+    cells.count(bodies.bodies.len())?;
+    for body in &bodies.bodies {
+        cells.count(body.len())?;
+        for cell in body.iter() {
+            let tag = match cell {
+                Cell::Exec(_) => 0,
+                Cell::Compiled(_) => 1,
+                Cell::Compiler(_) => 2,
+                Cell::Closure(_) => 3,
+                Cell::Data(_) => 4,
+                Cell::Call(..) => 5,
+                Cell::Recurse => 6,
+                Cell::Print(_) => 7,
+                Cell::ControlReturn => 8,
+                Cell::ControlBranch(_) => 9,
+                Cell::ControlBranchIfZero(_) => 10,
+                Cell::ControlBranchIfNotZero(_) => 11,
+                Cell::ControlLoop(_) => 12,
+                Cell::ControlPlusLoop(_) => 13,
+                Cell::ControlMinusLoop(_) => 14,
+                Cell::Inlined(..) => 15,
+            };
+            cells.u8(tag);
+            match cell {
+                Cell::Exec(_) | Cell::Compiled(_) | Cell::Compiler(_) | Cell::Closure(_) => {
+                    let key = native_key(cell).ok_or(Error::Executor)?;
+                    let index = match used_index.get(&key) {
+                        Some(index) => *index,
+                        None => {
+                            let name = names
+                                .get(&key)
+                                .ok_or(Error::Image("native word without a name".to_owned()))?;
+                            let index = count(used.len())?;
+                            used.push(name.clone());
+                            used_index.insert(key, index);
+                            index
+                        }
+                    };
+                    cells.u32(index);
+                }
+                Cell::Data(value) => cells.value(value)?,
+                Cell::Call(name, callee) => {
+                    cells.string(name)?;
+                    let index = bodies.index.get(&body_key(callee)).ok_or(Error::Executor)?;
+                    cells.count(*index)?;
+                }
+                Cell::Inlined(name, callee, step) => {
+                    cells.string(name)?;
+                    let index = bodies.index.get(&body_key(callee)).ok_or(Error::Executor)?;
+                    cells.count(*index)?;
+                    cells.i64(*step);
+                }
+                Cell::Print(text) => cells.string(text)?,
+                Cell::Recurse | Cell::ControlReturn => {}
+                Cell::ControlBranch(step)
+                | Cell::ControlBranchIfZero(step)
+                | Cell::ControlBranchIfNotZero(step)
+                | Cell::ControlLoop(step)
+                | Cell::ControlPlusLoop(step)
+                | Cell::ControlMinusLoop(step) => cells.i64(*step),
+            }
+        }
+    }
+
+    let mut image = Writer::default();
+    image.bytes.extend(IMAGE_MAGIC);
+    image.u32(IMAGE_VERSION);
+    image.u8(vm.tail_calls as u8);
+    image.u8(vm.optimize as u8);
+    image.u64(vm.max_call_depth as u64);
+    image.count(used.len())?;
+    for name in &used {
+        image.string(name)?;
+    }
+    image.bytes.extend(cells.bytes);
+    image.count(words.len())?;
+    for (name, code) in &words {
+        image.string(name)?;
+        let index = bodies.index.get(&body_key(code)).ok_or(Error::Executor)?;
+        image.count(*index)?;
+    }
+    image.stack(&vm.value_stack)?;
+    image.stack(&vm.return_stack)?;
+
+    output
+        .write_all(&image.bytes)
+        .and_then(|()| output.flush())
+        .map_err(|error| Error::Io(error.to_string()))
+}
+
+/// reads an image from memory
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+fn broken(what: &str) -> Error {
+    Error::Image(format!("broken image, {}", what))
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or(broken("unexpected end"))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    fn count(&mut self) -> Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.count()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| broken("invalid utf-8"))
+    }
+
+    /// a vector for `len` items that are at least `size` bytes each,
+    /// without trusting `len` more than the bytes that are left
+    fn vec<T>(&self, len: usize, size: usize) -> Vec<T> {
+        Vec::with_capacity(len.min((self.bytes.len() - self.pos) / size))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Variable> {
+        if depth > MAX_NESTING {
+            return Err(broken("values nested too deep"));
+        }
+        match self.u8()? {
+            0 => Ok(Variable::Int(self.i64()?)),
+            1 => Ok(Variable::String(self.string()?)),
+            2 => {
+                let len = self.count()?;
+                let mut values = self.vec(len, 1);
+                for _ in 0..len {
+                    values.push(self.value(depth + 1)?);
+                }
+                Ok(Variable::Array(values))
+            }
+            _ => Err(broken("unknown value")),
+        }
+    }
+
+    fn stack(&mut self) -> Result<Stack> {
+        let mut stack = Stack::new();
+        for _ in 0..self.count()? {
+            stack.push(self.value(0)?);
+        }
+        Ok(stack)
+    }
+
+    fn cell(&mut self, natives: &[Cell], bodies: &[Code]) -> Result<Cell> {
+        let tag = self.u8()?;
+        let cell = match tag {
+            0..=3 => {
+                let native = natives.get(self.count()?).ok_or(broken("unknown native"))?;
+                match (tag, native) {
+                    (0, Cell::Exec(word) | Cell::Compiled(word)) => Cell::Exec(*word),
+                    (1, Cell::Exec(word) | Cell::Compiled(word)) => Cell::Compiled(*word),
+                    (2, Cell::Compiler(_)) | (3, Cell::Closure(_)) => native.clone(),
+                    _ => return Err(broken("native of the wrong kind")),
+                }
+            }
+            4 => Cell::Data(self.value(0)?),
+            5 => {
+                let name = self.string()?;
+                let callee = bodies.get(self.count()?).ok_or(broken("unknown body"))?;
+                Cell::Call(name.into(), callee.clone())
+            }
+            6 => Cell::Recurse,
+            7 => Cell::Print(self.string()?.into()),
+            8 => Cell::ControlReturn,
+            9 => Cell::ControlBranch(self.i64()?),
+            10 => Cell::ControlBranchIfZero(self.i64()?),
+            11 => Cell::ControlBranchIfNotZero(self.i64()?),
+            12 => Cell::ControlLoop(self.i64()?),
+            13 => Cell::ControlPlusLoop(self.i64()?),
+            14 => Cell::ControlMinusLoop(self.i64()?),
+            15 => {
+                let name = self.string()?;
+                let callee = bodies.get(self.count()?).ok_or(broken("unknown body"))?;
+                Cell::Inlined(name.into(), callee.clone(), self.i64()?)
+            }
+            _ => return Err(broken("unknown cell")),
+        };
+        Ok(cell)
+    }
+}
+
+/// replaces the dictionary, the stacks and the settings of `vm`
+/// with an image from `input`, see the module documentation
+///
+/// nothing is changed if the image can't be loaded.
+/// `Error::MissingNative` names a native word that is unknown,
+/// `Error::Denied` one the `sandbox` denies and an image over its quotas
+/// fails like running the words would. `Error::Image` is a broken image
+/// or one of an other version.
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// vm.dictionary.add_closure("host", |vm| vm.write("host"));
+/// vm.eval(": twice host host ;").unwrap();
+/// let mut image = Vec::new();
+/// save_image(&vm, &mut image).unwrap();
+///
+/// let mut loaded = VM::new_null();
+/// loaded.value_stack.push(1);
+/// assert_eq!(
+///     load_image(&mut loaded, &mut &image[..]),
+///     Err(Error::MissingNative("host".to_owned()))
+/// );
+/// assert_eq!(loaded.value_stack.len(), 1);
+/// loaded.dictionary.add_closure("host", |vm| vm.write("relinked"));
+/// assert_eq!(load_image(&mut loaded, &mut &image[..]), Ok(()));
+///
+/// image[8] = 99;
+/// assert_eq!(
+///     load_image(&mut loaded, &mut &image[..]),
+///     Err(Error::Image("image version 99, expected 1".to_owned()))
+/// );
+/// assert_eq!(
+///     load_image(&mut loaded, &mut &b"#! forth"[..]),
+///     Err(Error::Image("not an image".to_owned()))
+/// );
+/// ```
+pub fn load_image(vm: &mut VM, input: &mut dyn Read) -> Result<()> {
+    let mut bytes = Vec::new();
+    input
+        .read_to_end(&mut bytes)
+        .map_err(|error| Error::Io(error.to_string()))?;
+    let mut reader = Reader {
+        bytes: &bytes,
+        pos: 0,
+    };
+
+    if reader.take(IMAGE_MAGIC.len()).ok() != Some(&IMAGE_MAGIC[..]) {
+        return Err(Error::Image("not an image".to_owned()));
+    }
+    let version = reader.u32()?;
+    if version != IMAGE_VERSION {
+        return Err(Error::Image(format!(
+            "image version {}, expected {}",
+            version, IMAGE_VERSION
+        )));
+    }
+    let tail_calls = reader.u8()? != 0;
+    let optimize = reader.u8()? != 0;
+    let max_call_depth = usize::try_from(reader.u64()?).unwrap_or(usize::MAX);
+
+    let mut known = natives(&vm.dictionary);
+    known.retain(|name, _| !vm.sandbox.denies(name));
+    let len = reader.count()?;
+    let mut natives = reader.vec(len, 4);
+    for _ in 0..len {
+        let name = reader.string()?;
+        let native = match known.get(&name) {
+            Some(native) => native,
+            None if vm.sandbox.denies(&name) => return Err(Error::Denied(name)),
+            None => return Err(Error::MissingNative(name)),
+        };
+        natives.push(native.clone());
+    }
+
+    let len = reader.count()?;
+    let mut bodies: Vec<Code> = reader.vec(len, 4);
+    for _ in 0..len {
+        let cells = reader.count()?;
+        let mut body = reader.vec(cells, 1);
+        for _ in 0..cells {
+            body.push(reader.cell(&natives, &bodies)?);
+        }
+        bodies.push(body.into());
+    }
+
+    let mut dictionary = Dictionary::new();
+    for _ in 0..reader.count()? {
+        let name = reader.string()?;
+        let body = bodies.get(reader.count()?).ok_or(broken("unknown body"))?;
+        dictionary.add_code(&name, body.clone());
+    }
+    let value_stack = reader.stack()?;
+    let return_stack = reader.stack()?;
+    if reader.pos != bytes.len() {
+        return Err(broken("trailing bytes"));
+    }
+    for body in &bodies {
+        vm.sandbox.check_literals(body)?;
+    }
+    vm.sandbox
+        .check(&value_stack, &return_stack, dictionary.cells())?;
+
+    vm.dictionary = dictionary;
+    vm.value_stack = value_stack;
+    vm.return_stack = return_stack;
+    vm.tail_calls = tail_calls;
+    vm.optimize = optimize;
+    vm.max_call_depth = max_call_depth;
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::{
+    fmt::Debug,
+    io::{BufRead, BufWriter, Write},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// input backend of the VM
+///
+/// anything that implements `BufRead` and `Send` can be used:
+/// a `BufReader<Stdin>`, a `BufReader<File>`, a `&[u8]`, ...
+/// wrap others in a `Local`
+pub type Input = Box<dyn BufRead + Send>;
+
+/// output backend of the VM
+///
+/// anything that implements `Write` and `Send` can be used.
+/// the VM buffers all output and flushes it
+/// at the end of `eval` and before reading input.
+pub type Output = BufWriter<Box<dyn Write + Send>>;
+
+/// diagnostics backend of the VM
+///
+/// receives errors and warnings, kept apart from the program
+/// output so the data written by a script stays clean.
+pub type Diagnostics = Box<dyn Write + Send>;
+
+/// in memory output backend
+///
+/// all clones share the same buffer, so a test can keep a clone
+/// and read back what the VM has written, also from an other thread.
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new(std::io::empty(), output.clone());
+///
+/// vm.write("hello");
+/// vm.flush();
+///
+/// assert_eq!(output.contents(), "hello");
+/// assert_eq!(output.take(), "hello");
+/// assert_eq!(output.contents(), "");
+/// ```
+#[derive(Clone, Default)]
+pub struct Capture {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture::default()
+    }
+
+    /// the buffer, even if a thread panicked while it wrote to it
+    fn buffer(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// everything written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer()).into_owned()
+    }
+
+    /// everything written so far, clears the buffer
+    pub fn take(&self) -> String {
+        let buffer = std::mem::take(&mut *self.buffer());
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Debug for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Capture").field(&self.contents()).finish()
+    }
+}
@@ -13,4 +13,26 @@ pub use stack::*;
 mod context;
 pub use context::*;
 
+mod io;
+pub use io::*;
+
+mod optimizer;
+pub use optimizer::*;
+
+mod hooks;
+pub use hooks::*;
+
+mod sandbox;
+pub use sandbox::*;
+
+mod call;
+pub use call::*;
+
+mod local;
+pub use local::*;
+
+pub mod image;
+pub use image::{load_image, save_image};
+
 pub mod builtins;
+pub mod repl;
@@ -0,0 +1,154 @@
+use crate::{Error, Hooks, Step};
+use std::{
+    io::{self, BufRead, Read, Write},
+    mem::ManuallyDrop,
+    thread::{self, ThreadId},
+};
+
+/// a value that is not `Send`, pinned to the thread that created it
+///
+/// it lets hooks, input or output that share state through `Rc` go
+/// into a VM, which has to be `Send`. on an other thread the value
+/// can't be reached: hooks are skipped and input or output fail with
+/// an io error. dropping it on an other thread leaks the value.
+///
+/// ```
+/// # use frust::*;
+/// # use std::{cell::Cell, rc::Rc};
+/// struct Count(Rc<Cell<usize>>);
+///
+/// impl Hooks for Count {
+///     fn enter(&mut self, _step: &Step) {
+///         self.0.set(self.0.get() + 1);
+///     }
+/// }
+///
+/// let mut vm = VM::new_null();
+/// builtins::add_core(&mut vm.dictionary);
+/// let count = Rc::new(Cell::new(0));
+/// vm.hooks = Some(Box::new(Local::new(Count(count.clone()))));
+///
+/// vm.eval("1 dup drop drop").unwrap();
+/// assert_eq!(count.get(), 3);
+///
+/// let mut vm = std::thread::spawn(move || {
+///     vm.eval("1 drop").unwrap();
+///     vm
+/// })
+/// .join()
+/// .unwrap();
+/// assert_eq!(count.get(), 3);
+/// vm.eval("1 drop").unwrap();
+/// assert_eq!(count.get(), 4);
+/// ```
+pub struct Local<T> {
+    value: ManuallyDrop<T>,
+    thread: ThreadId,
+}
+
+// SAFETY: the value is only reached, and dropped, on the thread that
+// created it, so it never leaves that thread even if the `Local` does
+unsafe impl<T> Send for Local<T> {}
+
+impl<T> Local<T> {
+    pub fn new(value: T) -> Self {
+        Local {
+            value: ManuallyDrop::new(value),
+            thread: thread::current().id(),
+        }
+    }
+
+    /// true on the thread that created the value
+    pub fn is_local(&self) -> bool {
+        thread::current().id() == self.thread
+    }
+
+    /// the value, `None` on other threads
+    pub fn get(&self) -> Option<&T> {
+        self.is_local().then(|| &*self.value)
+    }
+
+    /// the mutable value, `None` on other threads
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        match self.is_local() {
+            true => Some(&mut *self.value),
+            false => None,
+        }
+    }
+
+    fn io(&mut self) -> io::Result<&mut T> {
+        let error = || io::Error::other("used on an other thread");
+        self.get_mut().ok_or_else(error)
+    }
+}
+
+impl<T> Drop for Local<T> {
+    fn drop(&mut self) {
+        if self.is_local() {
+            // SAFETY: the value is dropped once, it isn't used after
+            unsafe { ManuallyDrop::drop(&mut self.value) }
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Local<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Local")
+            .field("thread", &self.thread)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Hooks> Hooks for Local<T> {
+    fn enter(&mut self, step: &Step) {
+        if let Some(hooks) = self.get_mut() {
+            hooks.enter(step)
+        }
+    }
+    fn before(&mut self, step: &Step) {
+        if let Some(hooks) = self.get_mut() {
+            hooks.before(step)
+        }
+    }
+    fn after(&mut self, step: &Step) {
+        if let Some(hooks) = self.get_mut() {
+            hooks.after(step)
+        }
+    }
+    fn exit(&mut self, step: &Step) {
+        if let Some(hooks) = self.get_mut() {
+            hooks.exit(step)
+        }
+    }
+    fn error(&mut self, step: &Step, error: &Error) {
+        if let Some(hooks) = self.get_mut() {
+            hooks.error(step, error)
+        }
+    }
+}
+
+impl<T: Read> Read for Local<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io()?.read(buf)
+    }
+}
+
+impl<T: BufRead> BufRead for Local<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.io()?.fill_buf()
+    }
+    fn consume(&mut self, amt: usize) {
+        if let Some(input) = self.get_mut() {
+            input.consume(amt)
+        }
+    }
+}
+
+impl<T: Write> Write for Local<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io()?.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.io()?.flush()
+    }
+}
@@ -1,53 +1,171 @@
 use frust::*;
+use std::{io::IsTerminal, path::PathBuf, process::ExitCode};
+
+const USAGE: &str = "\
+usage: frust [options] [file ...]
+
+runs each file and -e expression in the given order.
+without files or expressions the interactive REPL is started.
+
+options:
+  -e, --evaluate <code>  evaluate <code>
+  -i, --interactive      start the REPL after all files and expressions
+  -q, --quiet            batch mode, no banner, prompt or line editing
+  -I, --include <dir>    add <dir> to the include search path
+      --image <file>     load an image written by `save-image`
+  -h, --help             print this help
+
+exit status:
+  0 on success, 1 if an error was reported, 2 on invalid arguments.
+  `n bye` exits with status n.";
+
+/// something to run, in command line order
+enum Action {
+    Include(String),
+    Evaluate(String),
+    Image(String),
+}
+
+/// parsed command line
+#[derive(Default)]
+struct Options {
+    actions: Vec<Action>,
+    interactive: bool,
+    quiet: bool,
+    search_path: Vec<PathBuf>,
+    help: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> std::result::Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" | "--evaluate" => {
+                let code = args.next().ok_or(format!("{} needs an argument", arg))?;
+                options.actions.push(Action::Evaluate(code));
+            }
+            "--image" => {
+                let file = args.next().ok_or(format!("{} needs an argument", arg))?;
+                options.actions.push(Action::Image(file));
+            }
+            "-I" | "--include" => {
+                let dir = args.next().ok_or(format!("{} needs an argument", arg))?;
+                options.search_path.push(dir.into());
+            }
+            "-i" | "--interactive" => options.interactive = true,
+            "-q" | "--quiet" => options.quiet = true,
+            "-h" | "--help" => options.help = true,
+            "--" => options.actions.extend(args.by_ref().map(Action::Include)),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option {}", arg));
+            }
+            _ => options.actions.push(Action::Include(arg)),
+        }
+    }
+    if options.actions.is_empty() {
+        options.interactive = true;
+    }
+    Ok(options)
+}
+
+/// exit status for an `Error::Bye`, truncated like a shell does
+fn exit_code(code: i64) -> ExitCode {
+    ExitCode::from(code as u8)
+}
+
+/// where the REPL keeps its history
+fn history_file() -> Option<PathBuf> {
+    if let Some(file) = std::env::var_os("FRUST_HISTORY") {
+        return Some(file.into());
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".frust_history"))
+}
+
+/// read lines from the input until it ends
+///
+/// on a terminal this is the interactive REPL with line editing,
+/// otherwise lines are just evaluated one after the other
+fn repl(vm: &mut VM, quiet: bool) -> Option<ExitCode> {
+    let interactive = !quiet && std::io::stdin().is_terminal();
+    vm.handle_errors = true;
+    if !interactive {
+        while let Ok(Some(line)) = vm.read_line() {
+            if let Err(Error::Bye(code)) = vm.eval(&line) {
+                return Some(exit_code(code));
+            }
+        }
+        return None;
+    }
+
+    println!(
+        "frust {}, `bye` or ctrl-d to leave",
+        env!("CARGO_PKG_VERSION")
+    );
+    let history = match history_file() {
+        Some(file) => repl::History::open(file),
+        None => repl::History::new(),
+    };
+    let mut repl = repl::Repl::new(repl::Editor::new(history));
+    repl.terminal = Some(Box::new(repl::Stty::default()));
+    match repl.run(vm) {
+        Err(Error::Bye(code)) => return Some(exit_code(code)),
+        Err(error) => vm.diagnose(Severity::Error, &error.to_string()),
+        Ok(()) => {}
+    }
+    println!("bye!");
+    None
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("frust: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
 
-fn main() {
     let mut vm = VM::new_stdio();
-    vm.dictionary.add("+", Cell::Exec(builtins::plus));
-    vm.dictionary.add("-", Cell::Exec(builtins::minus));
-    vm.dictionary.add("*", Cell::Exec(builtins::times));
-    vm.dictionary.add("/", Cell::Exec(builtins::div));
-    vm.dictionary.add("mod", Cell::Exec(builtins::modulo));
-    vm.dictionary.add("\\", Cell::Exec(builtins::lcomment));
-    vm.dictionary.add("(", Cell::Exec(builtins::icomment));
-    vm.dictionary.add(".", Cell::Exec(builtins::dot));
-    vm.dictionary.add("cr", Cell::Exec(builtins::cr));
-    vm.dictionary.add("space", Cell::Exec(builtins::space));
-    vm.dictionary.add("1-", Cell::Exec(builtins::one_minus));
-    vm.dictionary.add("dup", Cell::Exec(builtins::dup));
-    vm.dictionary.add(".s", Cell::Exec(builtins::dot_s));
-    vm.dictionary.add("abs", Cell::Exec(builtins::abs));
-    vm.dictionary.add("=", Cell::Exec(builtins::eq));
-    vm.dictionary.add("max", Cell::Exec(builtins::max));
-    vm.dictionary.add("min", Cell::Exec(builtins::min));
-    vm.dictionary.add("nip", Cell::Exec(builtins::nip));
-    vm.dictionary
-        .add("roll", Cell::Exec(builtins::unimplemented));
-    vm.dictionary
-        .add("pick", Cell::Exec(builtins::unimplemented));
-    vm.dictionary.add("over", Cell::Exec(builtins::over));
-    vm.dictionary.add("tuck", Cell::Exec(builtins::tuck));
-    vm.dictionary.add("negate", Cell::Exec(builtins::negate));
-    vm.dictionary.add("dup", Cell::Exec(builtins::dup));
-    vm.dictionary.add("swap", Cell::Exec(builtins::swap));
-    vm.dictionary.add("rot", Cell::Exec(builtins::rot));
-    vm.dictionary.add("drop", Cell::Exec(builtins::drop));
-    vm.dictionary.add("?dup", Cell::Exec(builtins::qdup));
-    vm.dictionary.add("i", Cell::Exec(builtins::i));
-    vm.dictionary.add("j", Cell::Exec(builtins::j));
-    vm.dictionary
-        .add("if", Cell::Compiler(builtins::compiletime_if));
-    vm.dictionary
-        .add("do", Cell::Compiler(builtins::compiletime_do));
-    vm.dictionary
-        .add(".\"", Cell::Compiler(builtins::compiletime_dot_q));
-
-    loop {
-        let mut buffer = String::new();
-        let _ = match (vm.read)(&mut buffer) {
-            Ok(0) => break,
-            Ok(_) => vm.eval(&buffer),
-            Err(_) => break,
+    builtins::add_all(&mut vm.dictionary);
+    vm.files.search_path = options.search_path;
+
+    // scripts stop at the first error
+    vm.handle_errors = false;
+    for action in options.actions {
+        let result = match action {
+            Action::Include(file) => vm.include(&file),
+            Action::Evaluate(code) => vm.eval(&code),
+            Action::Image(file) => std::fs::File::open(&file)
+                .map_err(|error| Error::Io(format!("{}: {}", file, error)))
+                .and_then(|mut image| load_image(&mut vm, &mut image)),
         };
+        match result {
+            Ok(()) => {}
+            Err(Error::Bye(code)) => return exit_code(code),
+            Err(error) => {
+                vm.diagnose(Severity::Error, &error.to_string());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if options.interactive {
+        if let Some(code) = repl(&mut vm, options.quiet) {
+            return code;
+        }
+    }
+    // like at the end of an included file
+    if matches!(vm.state, State::FIllBuffer) {
+        let error = Error::Parser("unterminated definition".to_owned());
+        vm.diagnose(Severity::Error, &error.to_string());
+        return ExitCode::FAILURE;
+    }
+    if vm.error_count > 0 {
+        return ExitCode::FAILURE;
     }
-    println!("bye!")
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,347 @@
+use crate::{builtins, Cell, Result, Variable, VM};
+
+/// definitions with at most this many cells are inlined into their callers
+pub const INLINE_LIMIT: usize = 4;
+
+/// a cell with the absolute index its jump goes to
+#[derive(Clone)]
+struct Op {
+    cell: Cell,
+    target: Option<usize>,
+}
+
+/// peephole optimizer for the body of a colon definition
+///
+/// - calls of small definitions are replaced by their body, after an
+///   `Inlined` cell so the definition is still seen by the debugger,
+///   the profiler and `hooks`
+/// - arithmetic on constants is folded
+/// - `dup +`, `1 +` and `1 -` become one of the `SUPERINSTRUCTIONS`
+/// - branches on constant flags and cells that can't be reached are removed
+///
+/// the optimized body behaves exactly like the original one,
+/// including the errors it raises
+///
+/// ```
+/// # use frust::*;
+/// let code = vec![
+///     Cell::Data(Variable::Int(2)),
+///     Cell::Data(Variable::Int(3)),
+///     Cell::Exec(builtins::plus),
+///     Cell::Exec(builtins::dup),
+///     Cell::Exec(builtins::plus),
+/// ];
+/// assert_eq!(optimize(code), vec![Cell::Data(Variable::Int(10))]);
+///
+/// let code = vec![
+///     Cell::Exec(builtins::dup),
+///     Cell::Exec(builtins::plus),
+///     Cell::Data(Variable::Int(0)),
+///     Cell::ControlBranchIfZero(3),
+///     Cell::Exec(builtins::dot),
+///     Cell::ControlBranch(1),
+/// ];
+/// assert_eq!(optimize(code), vec![Cell::Exec(builtins::runtime_two_star)]);
+/// ```
+pub fn optimize(code: Vec<Cell>) -> Vec<Cell> {
+    let Some(ops) = decode(&code) else {
+        return code;
+    };
+    let mut ops = inline(ops);
+    loop {
+        let mut changed = false;
+        for pass in [fold, superinstruction, constant_branch] {
+            let (rewritten, pass_changed) = rewrite(ops, pass);
+            ops = rewritten;
+            changed |= pass_changed;
+        }
+        let reachable = reachable(&ops);
+        let (rewritten, pass_changed) = rewrite(ops, |_, i| (!reachable[i]).then(|| (1, vec![])));
+        ops = rewritten;
+        changed |= pass_changed;
+        if !changed {
+            return encode(ops);
+        }
+    }
+}
+
+/// jump offset of a cell, relative to the cell itself
+fn offset(cell: &Cell) -> Option<i64> {
+    match cell {
+        Cell::ControlBranch(step)
+        | Cell::ControlBranchIfZero(step)
+        | Cell::ControlBranchIfNotZero(step)
+        | Cell::ControlLoop(step)
+        | Cell::ControlPlusLoop(step)
+        | Cell::ControlMinusLoop(step)
+        | Cell::Inlined(_, _, step) => Some(*step),
+        _ => None,
+    }
+}
+
+/// the same cell jumping by `step`
+fn with_offset(cell: Cell, step: i64) -> Cell {
+    match cell {
+        Cell::ControlBranch(_) => Cell::ControlBranch(step),
+        Cell::ControlBranchIfZero(_) => Cell::ControlBranchIfZero(step),
+        Cell::ControlBranchIfNotZero(_) => Cell::ControlBranchIfNotZero(step),
+        Cell::ControlLoop(_) => Cell::ControlLoop(step),
+        Cell::ControlPlusLoop(_) => Cell::ControlPlusLoop(step),
+        Cell::ControlMinusLoop(_) => Cell::ControlMinusLoop(step),
+        Cell::Inlined(name, code, _) => Cell::Inlined(name, code, step),
+        cell => cell,
+    }
+}
+
+/// relative offsets to absolute targets, `None` if a jump leaves the body
+fn decode(code: &[Cell]) -> Option<Vec<Op>> {
+    code.iter()
+        .enumerate()
+        .map(|(i, cell)| {
+            let target = match offset(cell) {
+                Some(step) => {
+                    let target = usize::try_from(i as i64 + step).ok()?;
+                    (target <= code.len()).then_some(target)?;
+                    Some(target)
+                }
+                None => None,
+            };
+            Some(Op {
+                cell: cell.clone(),
+                target,
+            })
+        })
+        .collect()
+}
+
+fn encode(ops: Vec<Op>) -> Vec<Cell> {
+    ops.into_iter()
+        .enumerate()
+        .map(|(i, op)| match op.target {
+            Some(target) => with_offset(op.cell, target as i64 - i as i64),
+            None => op.cell,
+        })
+        .collect()
+}
+
+/// replaces runs of cells, jumps are moved along
+///
+/// `pass` gets all cells and the current index and may return how many
+/// cells to replace with what. a run is only replaced if no jump goes
+/// into its middle. jumps to removed cells go to the cell after them.
+fn rewrite<F>(ops: Vec<Op>, mut pass: F) -> (Vec<Op>, bool)
+where
+    F: FnMut(&[Op], usize) -> Option<(usize, Vec<Op>)>,
+{
+    let mut is_target = vec![false; ops.len() + 1];
+    for target in ops.iter().filter_map(|op| op.target) {
+        is_target[target] = true;
+    }
+
+    let mut out = Vec::with_capacity(ops.len());
+    let mut map = vec![0; ops.len() + 1];
+    let mut changed = false;
+    let mut i = 0;
+    while i < ops.len() {
+        if let Some((n, replacement)) = pass(&ops, i) {
+            if n > 0 && i + n <= ops.len() && !(i + 1..i + n).any(|j| is_target[j]) {
+                map[i..i + n].fill(out.len());
+                out.extend(replacement);
+                changed = true;
+                i += n;
+                continue;
+            }
+        }
+        map[i] = out.len();
+        out.push(ops[i].clone());
+        i += 1;
+    }
+    map[ops.len()] = out.len();
+
+    for op in out.iter_mut() {
+        if let Some(target) = op.target.as_mut() {
+            *target = map[*target];
+        }
+    }
+    (out, changed)
+}
+
+/// a body that can be copied into its caller
+fn inlinable(code: &[Cell]) -> bool {
+    code.len() <= INLINE_LIMIT
+        && !code
+            .iter()
+            .any(|cell| matches!(cell, Cell::Recurse | Cell::ControlReturn))
+}
+
+/// replaces calls of small definitions with an `Inlined` cell and their body
+///
+/// the `Inlined` cell jumps to the end of the body, so later passes don't
+/// move cells across it
+fn inline(ops: Vec<Op>) -> Vec<Op> {
+    let bodies: Vec<Option<Vec<Op>>> = ops
+        .iter()
+        .map(|op| match &op.cell {
+            Cell::Call(_, code) if inlinable(code) => decode(code),
+            _ => None,
+        })
+        .collect();
+
+    // where each cell starts after inlining
+    let mut start = Vec::with_capacity(ops.len() + 1);
+    let mut len = 0;
+    for body in bodies.iter() {
+        start.push(len);
+        len += body.as_ref().map_or(1, |body| 1 + body.len());
+    }
+    start.push(len);
+
+    let mut out = Vec::with_capacity(len);
+    for (i, (op, body)) in ops.into_iter().zip(bodies).enumerate() {
+        match (body, op.cell) {
+            (Some(body), Cell::Call(name, code)) => {
+                out.push(Op {
+                    cell: Cell::Inlined(name, code, 0),
+                    target: Some(start[i + 1]),
+                });
+                out.extend(body.into_iter().map(|inner| Op {
+                    target: inner.target.map(|target| start[i] + 1 + target),
+                    ..inner
+                }));
+            }
+            (_, cell) => out.push(Op {
+                cell,
+                target: op.target.map(|target| start[target]),
+            }),
+        }
+    }
+    out
+}
+
+fn is(word: fn(&mut VM) -> Result<()>, builtin: fn(&mut VM) -> Result<()>) -> bool {
+    std::ptr::fn_addr_eq(word, builtin)
+}
+
+fn data(value: i64) -> Op {
+    Op {
+        cell: Cell::Data(Variable::Int(value)),
+        target: None,
+    }
+}
+
+fn exec(word: fn(&mut VM) -> Result<()>) -> Op {
+    Op {
+        cell: Cell::Exec(word),
+        target: None,
+    }
+}
+
+/// `2 3 +` to `5`, as long as nothing overflows
+fn fold(ops: &[Op], i: usize) -> Option<(usize, Vec<Op>)> {
+    let cells: Vec<&Cell> = ops[i..].iter().take(3).map(|op| &op.cell).collect();
+    match cells[..] {
+        [Cell::Data(Variable::Int(a)), Cell::Data(Variable::Int(b)), Cell::Exec(word)] => {
+            let value = if is(*word, builtins::plus) {
+                a.checked_add(*b)
+            } else if is(*word, builtins::minus) {
+                a.checked_sub(*b)
+            } else if is(*word, builtins::times) {
+                a.checked_mul(*b)
+            } else {
+                None
+            };
+            value.map(|value| (3, vec![data(value)]))
+        }
+        [Cell::Data(Variable::Int(a)), Cell::Exec(word), ..] => {
+            let value = if is(*word, builtins::runtime_one_plus) {
+                a.checked_add(1)
+            } else if is(*word, builtins::runtime_one_minus) || is(*word, builtins::one_minus) {
+                a.checked_sub(1)
+            } else if is(*word, builtins::runtime_two_star) {
+                a.checked_mul(2)
+            } else {
+                None
+            };
+            value.map(|value| (2, vec![data(value)]))
+        }
+        _ => None,
+    }
+}
+
+/// common pairs to a single word
+fn superinstruction(ops: &[Op], i: usize) -> Option<(usize, Vec<Op>)> {
+    let cells: Vec<&Cell> = ops[i..].iter().take(2).map(|op| &op.cell).collect();
+    let word = match cells[..] {
+        [Cell::Exec(first), Cell::Exec(second)]
+            if is(*first, builtins::dup) && is(*second, builtins::plus) =>
+        {
+            builtins::runtime_two_star
+        }
+        [Cell::Data(Variable::Int(1)), Cell::Exec(second)] if is(*second, builtins::plus) => {
+            builtins::runtime_one_plus
+        }
+        [Cell::Data(Variable::Int(1)), Cell::Exec(second)] if is(*second, builtins::minus) => {
+            builtins::runtime_one_minus
+        }
+        _ => return None,
+    };
+    Some((2, vec![exec(word)]))
+}
+
+/// branches on a constant flag and jumps to the next cell
+fn constant_branch(ops: &[Op], i: usize) -> Option<(usize, Vec<Op>)> {
+    match (&ops[i], ops.get(i + 1)) {
+        (
+            Op {
+                cell: Cell::Data(Variable::Int(flag)),
+                ..
+            },
+            Some(branch),
+        ) => {
+            let taken = match branch.cell {
+                Cell::ControlBranchIfZero(_) => *flag == 0,
+                Cell::ControlBranchIfNotZero(_) => *flag != 0,
+                _ => return None,
+            };
+            if taken {
+                let jump = Op {
+                    cell: Cell::ControlBranch(0),
+                    target: branch.target,
+                };
+                Some((2, vec![jump]))
+            } else {
+                Some((2, vec![]))
+            }
+        }
+        (
+            Op {
+                cell: Cell::ControlBranch(_),
+                target: Some(target),
+            },
+            _,
+        ) if *target == i + 1 => Some((1, vec![])),
+        _ => None,
+    }
+}
+
+/// cells that can be reached from the start of the body
+fn reachable(ops: &[Op]) -> Vec<bool> {
+    let mut reachable = vec![false; ops.len()];
+    let mut todo = vec![0];
+    while let Some(i) = todo.pop() {
+        if i >= ops.len() || reachable[i] {
+            continue;
+        }
+        reachable[i] = true;
+        let op = &ops[i];
+        match op.cell {
+            Cell::ControlReturn => {}
+            Cell::ControlBranch(_) => todo.extend(op.target),
+            _ => {
+                todo.push(i + 1);
+                todo.extend(op.target);
+            }
+        }
+    }
+    reachable
+}
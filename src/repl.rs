@@ -0,0 +1,595 @@
+use std::{
+    fs::OpenOptions,
+    io::{Read, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use crate::{Dictionary, Error, Result, State, VM};
+
+/// prompt while nothing is pending
+pub const PROMPT: &str = "> ";
+/// prompt while a colon definition is still open
+pub const CONTINUE_PROMPT: &str = "| ";
+
+/// lines entered so far
+///
+/// if a file is given, the history is loaded from it
+/// and every new line is appended to it
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    file: Option<PathBuf>,
+}
+
+impl History {
+    /// in memory history
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// history that is persisted in `file`
+    pub fn open(file: PathBuf) -> History {
+        let entries = std::fs::read_to_string(&file)
+            .map(|text| text.lines().map(|line| line.to_owned()).collect())
+            .unwrap_or_default();
+        History {
+            entries,
+            file: Some(file),
+        }
+    }
+
+    /// remember a line, empty lines and repetitions are skipped
+    ///
+    /// ```
+    /// # use frust::repl::History;
+    /// let mut history = History::new();
+    /// history.push("1 2 +");
+    /// history.push("1 2 +");
+    /// history.push("  ");
+    ///
+    /// assert_eq!(history.entries(), ["1 2 +"]);
+    /// ```
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() || self.entries.last().map(|last| last.as_str()) == Some(line) {
+            return;
+        }
+        self.entries.push(line.to_owned());
+        if let Some(file) = &self.file {
+            // history is a convenience, failing to save it is not an error
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(file) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+
+/// switches the terminal in and out of raw mode for `Repl::run`
+///
+/// the terminal is raw only while a line is read, programs run in the
+/// normal line-by-line mode so `ctrl-c` interrupts them and input echoes.
+///
+/// ```
+/// # use frust::*;
+/// use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+///
+/// #[derive(Debug)]
+/// struct Fake(Arc<AtomicBool>);
+/// impl repl::Terminal for Fake {
+///     fn raw(&mut self) -> std::io::Result<()> {
+///         Ok(self.0.store(true, Ordering::SeqCst))
+///     }
+///     fn restore(&mut self) -> std::io::Result<()> {
+///         Ok(self.0.store(false, Ordering::SeqCst))
+///     }
+/// }
+///
+/// let raw = Arc::new(AtomicBool::new(false));
+/// let output = Capture::new();
+/// let mut vm = VM::new("raw? .\r".as_bytes(), output.clone());
+/// builtins::add_all(&mut vm.dictionary);
+/// let seen = raw.clone();
+/// vm.dictionary.add_closure("raw?", move |vm| {
+///     vm.value_stack.push(seen.load(Ordering::SeqCst) as i64);
+///     Ok(())
+/// });
+///
+/// let mut repl = repl::Repl::default();
+/// repl.terminal = Some(Box::new(Fake(raw.clone())));
+/// repl.run(&mut vm).unwrap();
+///
+/// let screen = output.take().replace("\x1b[K", "");
+/// assert!(screen.contains("> raw? . 0  ok\n"));
+/// assert!(!raw.load(Ordering::SeqCst));
+/// ```
+pub trait Terminal: std::fmt::Debug {
+    /// put the terminal into raw mode
+    fn raw(&mut self) -> std::io::Result<()>;
+    /// go back to the mode from before `raw`
+    fn restore(&mut self) -> std::io::Result<()>;
+}
+
+/// the terminal of the process, switched with `stty`
+///
+/// in raw mode keys are read one by one without echo and `ctrl-c` is just a key.
+/// output is still processed, so `\n` starts a new line for the program output.
+/// uses `stty`, so it only works on unix like systems
+#[derive(Debug, Default)]
+pub struct Stty {
+    saved: Option<String>,
+}
+
+impl Terminal for Stty {
+    fn raw(&mut self) -> std::io::Result<()> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1", "time", "0"])?;
+        self.saved = Some(saved.trim().to_owned());
+        Ok(())
+    }
+
+    fn restore(&mut self) -> std::io::Result<()> {
+        match self.saved.take() {
+            Some(saved) => stty(&[&saved]).map(drop),
+            None => Ok(()),
+        }
+    }
+}
+
+/// keeps a terminal in raw mode until it is dropped, also on a panic
+struct RawMode<'a>(&'a mut Box<dyn Terminal>);
+
+impl<'a> RawMode<'a> {
+    fn enable(terminal: &'a mut Box<dyn Terminal>) -> Option<RawMode<'a>> {
+        terminal.raw().ok()?;
+        Some(RawMode(terminal))
+    }
+}
+
+impl Drop for RawMode<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.restore();
+    }
+}
+
+fn stty(args: &[&str]) -> std::io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// a decoded key press
+#[derive(Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Tab,
+    KillToEnd,
+    KillToStart,
+    KillWord,
+    /// `ctrl-d`
+    EndOfInput,
+    /// `ctrl-c`
+    Cancel,
+    Ignore,
+}
+
+fn read_byte(input: &mut dyn Read) -> std::io::Result<Option<u8>> {
+    let mut byte = [0u8];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// decode the next key press, `None` at the end of the input
+fn read_key(input: &mut dyn Read) -> std::io::Result<Option<Key>> {
+    let Some(byte) = read_byte(input)? else {
+        return Ok(None);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        b'\t' => Key::Tab,
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 => Key::Cancel,
+        0x04 => Key::EndOfInput,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x0b => Key::KillToEnd,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x15 => Key::KillToStart,
+        0x17 => Key::KillWord,
+        0x1b => read_escape(input)?,
+        0x00..=0x1f => Key::Ignore,
+        _ => read_utf8(input, byte)?,
+    };
+    Ok(Some(key))
+}
+
+/// decode an ANSI escape sequence after the `ESC`
+fn read_escape(input: &mut dyn Read) -> std::io::Result<Key> {
+    match read_byte(input)? {
+        Some(b'[') | Some(b'O') => {}
+        _ => return Ok(Key::Ignore),
+    }
+    let mut parameter = Vec::new();
+    while let Some(byte) = read_byte(input)? {
+        if (0x40..=0x7e).contains(&byte) {
+            return Ok(match (byte, parameter.as_slice()) {
+                (b'A', _) => Key::Up,
+                (b'B', _) => Key::Down,
+                (b'C', _) => Key::Right,
+                (b'D', _) => Key::Left,
+                (b'H', _) | (b'~', b"1") | (b'~', b"7") => Key::Home,
+                (b'F', _) | (b'~', b"4") | (b'~', b"8") => Key::End,
+                (b'~', b"3") => Key::Delete,
+                _ => Key::Ignore,
+            });
+        }
+        parameter.push(byte);
+    }
+    Ok(Key::Ignore)
+}
+
+/// decode a utf-8 character starting with `first`
+fn read_utf8(input: &mut dyn Read, first: u8) -> std::io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match read_byte(input)? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    Ok(String::from_utf8(bytes)
+        .ok()
+        .and_then(|text| text.chars().next())
+        .map(Key::Char)
+        .unwrap_or(Key::Ignore))
+}
+
+/// longest common prefix of all candidates
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = candidates.first().cloned().unwrap_or_default();
+    for candidate in candidates {
+        while !candidate.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
+
+/// all dictionary words that start with `prefix`, sorted
+///
+/// ```
+/// # use frust::*;
+/// let mut dict = Dictionary::new();
+/// builtins::add_core(&mut dict);
+///
+/// assert_eq!(repl::complete(&dict, "DR"), vec!["drop"]);
+/// assert_eq!(repl::complete(&dict, "ma"), vec!["max"]);
+/// ```
+pub fn complete(dictionary: &Dictionary, prefix: &str) -> Vec<String> {
+    let prefix = prefix.to_lowercase();
+    let mut names: Vec<String> = dictionary
+        .names()
+        .filter(|name| name.starts_with(&prefix))
+        .map(|name| name.to_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// minimal line editor using ANSI escape sequences
+///
+/// - `left`/`right`, `home`/`end`, `ctrl-a`/`ctrl-e` move the cursor
+/// - `up`/`down` walk through the history
+/// - `ctrl-k`, `ctrl-u` and `ctrl-w` delete to the end, the start or the previous word
+/// - `tab` completes the word under the cursor
+/// - `ctrl-c` cancels the line, `ctrl-d` on an empty line ends the input
+#[derive(Debug, Default)]
+pub struct Editor {
+    pub history: History,
+}
+
+/// the line that is being edited
+struct Line<'a> {
+    prompt: &'a str,
+    text: Vec<char>,
+    cursor: usize,
+}
+
+impl Line<'_> {
+    fn set(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+    }
+
+    fn as_string(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    fn word_start(&self) -> usize {
+        let mut start = self.cursor;
+        while start > 0 && !self.text[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        start
+    }
+
+    fn redraw(&self, output: &mut dyn Write) -> std::io::Result<()> {
+        write!(output, "\r{}{}\x1b[K", self.prompt, self.as_string())?;
+        let back = self.text.len() - self.cursor;
+        if back > 0 {
+            write!(output, "\x1b[{}D", back)?;
+        }
+        output.flush()
+    }
+}
+
+impl Editor {
+    pub fn new(history: History) -> Editor {
+        Editor { history }
+    }
+
+    /// read and edit one line
+    ///
+    /// `complete` returns all words that start with the given prefix.
+    /// returns `None` at the end of the input.
+    ///
+    /// ```
+    /// # use frust::repl::{Editor, History};
+    /// let mut editor = Editor::new(History::new());
+    /// let mut output = Vec::new();
+    /// let complete = |prefix: &str| vec!["swap".to_owned()];
+    ///
+    /// // type `1 x`, go left, delete the `x`, add `2 sw` and complete it
+    /// let mut keys = "1 x\x1b[D\x1b[3~2 sw\t\r".as_bytes();
+    /// let line = editor.read_line("> ", &mut keys, &mut output, &complete);
+    /// assert_eq!(line.unwrap(), Some("1 2 swap ".to_owned()));
+    ///
+    /// // arrow up recalls the last line
+    /// let mut keys = "\x1b[A\x08\x08\x08\x08\x08\r".as_bytes();
+    /// let line = editor.read_line("> ", &mut keys, &mut output, &complete);
+    /// assert_eq!(line.unwrap(), Some("1 2 ".to_owned()));
+    /// ```
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+        complete: &dyn Fn(&str) -> Vec<String>,
+    ) -> std::io::Result<Option<String>> {
+        let mut line = Line {
+            prompt,
+            text: Vec::new(),
+            cursor: 0,
+        };
+        // position in the history, `entries.len()` is the new line
+        let mut position = self.history.entries().len();
+        let mut draft = String::new();
+
+        line.redraw(output)?;
+        loop {
+            let Some(key) = read_key(input)? else {
+                if line.text.is_empty() {
+                    return Ok(None);
+                }
+                break;
+            };
+            match key {
+                Key::Char(c) => {
+                    line.text.insert(line.cursor, c);
+                    line.cursor += 1;
+                }
+                Key::Enter => break,
+                Key::Backspace if line.cursor > 0 => {
+                    line.cursor -= 1;
+                    line.text.remove(line.cursor);
+                }
+                Key::Delete if line.cursor < line.text.len() => {
+                    line.text.remove(line.cursor);
+                }
+                Key::Left if line.cursor > 0 => line.cursor -= 1,
+                Key::Right if line.cursor < line.text.len() => line.cursor += 1,
+                Key::Home => line.cursor = 0,
+                Key::End => line.cursor = line.text.len(),
+                Key::Up if position > 0 => {
+                    if position == self.history.entries().len() {
+                        draft = line.as_string();
+                    }
+                    position -= 1;
+                    line.set(&self.history.entries()[position]);
+                }
+                Key::Down if position < self.history.entries().len() => {
+                    position += 1;
+                    match self.history.entries().get(position) {
+                        Some(entry) => line.set(entry),
+                        None => line.set(&draft),
+                    }
+                }
+                Key::KillToEnd => line.text.truncate(line.cursor),
+                Key::KillToStart => {
+                    line.text.drain(..line.cursor);
+                    line.cursor = 0;
+                }
+                Key::KillWord => {
+                    let mut start = line.cursor;
+                    while start > 0 && line.text[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    while start > 0 && !line.text[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    line.text.drain(start..line.cursor);
+                    line.cursor = start;
+                }
+                Key::Tab => self.complete(&mut line, output, complete)?,
+                Key::EndOfInput if line.text.is_empty() => {
+                    write!(output, "\r\n")?;
+                    return Ok(None);
+                }
+                Key::EndOfInput if line.cursor < line.text.len() => {
+                    line.text.remove(line.cursor);
+                }
+                Key::Cancel => {
+                    write!(output, "^C\r\n")?;
+                    line.text.clear();
+                    line.cursor = 0;
+                    position = self.history.entries().len();
+                }
+                _ => {}
+            }
+            line.redraw(output)?;
+        }
+        // stay on the line, so the output follows the input like in a classic forth
+        write!(output, " ")?;
+        output.flush()?;
+        let text = line.as_string();
+        self.history.push(&text);
+        Ok(Some(text))
+    }
+
+    fn complete(
+        &self,
+        line: &mut Line,
+        output: &mut dyn Write,
+        complete: &dyn Fn(&str) -> Vec<String>,
+    ) -> std::io::Result<()> {
+        let start = line.word_start();
+        let prefix: String = line.text[start..line.cursor].iter().collect();
+        let candidates = complete(&prefix);
+        let mut completion = match candidates.as_slice() {
+            [] => return Ok(()),
+            [single] => format!("{} ", single),
+            _ => common_prefix(&candidates),
+        };
+        if completion.chars().count() <= prefix.chars().count() {
+            // nothing to add, show what is possible
+            write!(output, "\r\n{}\r\n", candidates.join("  "))?;
+            completion = prefix;
+        }
+        let completion: Vec<char> = completion.chars().collect();
+        let len = completion.len();
+        line.text.splice(start..line.cursor, completion);
+        line.cursor = start + len;
+        Ok(())
+    }
+}
+
+/// interactive read-eval-print loop
+///
+/// - prompts with `PROMPT`, or `CONTINUE_PROMPT` inside a colon definition
+/// - prints ` ok` after each line, or ` compiled` inside a colon definition
+///
+/// ```
+/// # use frust::*;
+/// let output = Capture::new();
+/// let mut vm = VM::new(": sq\rdup * ;\r3 sq .\r".as_bytes(), output.clone());
+/// builtins::add_all(&mut vm.dictionary);
+///
+/// let mut repl = repl::Repl::new(repl::Editor::new(repl::History::new()));
+/// repl.run(&mut vm).unwrap();
+///
+/// let screen = output.take().replace("\x1b[K", "");
+/// assert!(screen.contains("> : sq  compiled\n"));
+/// assert!(screen.contains("| dup * ;  ok\n"));
+/// assert!(screen.contains("> 3 sq . 9  ok\n"));
+/// ```
+#[derive(Debug, Default)]
+pub struct Repl {
+    pub editor: Editor,
+    /// put into raw mode while a line is read
+    pub terminal: Option<Box<dyn Terminal>>,
+}
+
+impl Repl {
+    pub fn new(editor: Editor) -> Repl {
+        Repl {
+            editor,
+            terminal: None,
+        }
+    }
+
+    /// read, evaluate and print until the input ends
+    ///
+    /// errors are reported by the VM, only `Error::Bye`
+    /// and failing I/O end the loop early.
+    /// characters `KEY` read ahead are edited before the rest of the input.
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let output = Capture::new();
+    /// let mut vm = VM::new("key drop\r2 3 .\n".as_bytes(), output.clone());
+    /// builtins::add_all(&mut vm.dictionary);
+    ///
+    /// repl::Repl::default().run(&mut vm).unwrap();
+    ///
+    /// let screen = output.take().replace("\x1b[K", "");
+    /// assert!(screen.contains(">  3 . 3  ok\n"));
+    /// ```
+    pub fn run(&mut self, vm: &mut VM) -> Result<()> {
+        vm.handle_errors = true;
+        loop {
+            let prompt = match vm.state {
+                State::FIllBuffer => CONTINUE_PROMPT,
+                _ => PROMPT,
+            };
+            let pending: String = vm.key_buffer.drain(..).collect();
+            let mut pending = pending.as_bytes();
+            let dictionary = &vm.dictionary;
+            let raw = self.terminal.as_mut().and_then(RawMode::enable);
+            let line = self
+                .editor
+                .read_line(
+                    prompt,
+                    &mut (&mut pending).chain(&mut vm.input),
+                    &mut vm.output,
+                    &|prefix| complete(dictionary, prefix),
+                )
+                .map_err(|error| Error::Io(error.to_string()));
+            drop(raw);
+            // what is left after the line stays for `KEY`
+            vm.key_buffer
+                .extend(String::from_utf8_lossy(pending).chars());
+            let line = line?;
+            let Some(line) = line else {
+                return Ok(());
+            };
+
+            let errors = vm.error_count;
+            vm.eval(&line)?;
+            if vm.error_count == errors {
+                match vm.state {
+                    State::FIllBuffer => vm.write(" compiled\n")?,
+                    _ => vm.write(" ok\n")?,
+                }
+                vm.flush()?;
+            }
+        }
+    }
+}
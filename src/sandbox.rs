@@ -0,0 +1,304 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use crate::{builtins::WordSet, Cell, Dictionary, Error, Result, Stack, Variable, VM};
+
+/// what scripts may do, set `VM::sandbox` before they run
+///
+/// denied words can neither be used nor defined. `VM::add_word_set` leaves
+/// them out and looking them up fails with `Error::Denied`, even if they
+/// were added before. natives are copied into the words that use them, so
+/// running a denied native fails the same way, also in words compiled before
+/// the sandbox was set or in a shared base.
+///
+/// every quota that is `Some` is checked after each cell, each interpreted
+/// number and each definition, a breach ends execution with an error of its own.
+/// both stacks are emptied then, like `ABORT` does. natives that read text
+/// check the strings and memory quotas before, with `VM::reserve_string`.
+///
+/// the default denies nothing and has no quotas.
+///
+/// ```
+/// # use frust::*;
+/// let mut vm = VM::new_null();
+/// vm.handle_errors = false;
+/// vm.sandbox = Sandbox::untrusted();
+/// vm.sandbox.max_value_stack = Some(3);
+/// vm.add_word_set(builtins::WordSet::Core);
+/// vm.add_word_set(builtins::WordSet::File);
+///
+/// let denied = Err(Error::Denied("include".to_owned()));
+/// assert_eq!(vm.eval("include secret.fr"), denied);
+/// vm.cancel();
+/// assert_eq!(vm.eval(": include ;"), denied);
+/// vm.cancel();
+///
+/// assert_eq!(vm.eval("1 2 3 4"), Err(Error::ValueStackOverflow));
+/// assert!(vm.value_stack.is_empty());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Sandbox {
+    /// lowercase names of the denied words
+    pub denied: HashSet<String>,
+    /// values on the value stack
+    pub max_value_stack: Option<usize>,
+    /// values on the return stack
+    pub max_return_stack: Option<usize>,
+    /// cells of all words in the dictionary, see `Dictionary::cells`
+    pub max_dictionary: Option<usize>,
+    /// bytes of a string, also inside arrays
+    pub max_string: Option<usize>,
+    /// elements of an array
+    pub max_array: Option<usize>,
+    /// bytes held by the stacks and the dictionary, roughly
+    pub max_memory: Option<usize>,
+}
+
+impl Sandbox {
+    /// for scripts that must not touch the host
+    ///
+    /// denies the file word set and the tools that leave forth,
+    /// read the input or change the VM. the quotas are generous
+    /// for scripts but keep the memory of the host bounded.
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let sandbox = Sandbox::untrusted();
+    /// assert!(sandbox.denies("OPEN-FILE"));
+    /// assert!(sandbox.denies("bye"));
+    /// assert!(!sandbox.denies(".s"));
+    /// assert!(!sandbox.denies("dup"));
+    /// ```
+    pub fn untrusted() -> Sandbox {
+        let mut sandbox = Sandbox {
+            max_value_stack: Some(1 << 16),
+            max_return_stack: Some(1 << 16),
+            max_dictionary: Some(1 << 16),
+            max_string: Some(1 << 16),
+            max_array: Some(1 << 16),
+            max_memory: Some(1 << 24),
+            ..Sandbox::default()
+        };
+        sandbox.deny_set(WordSet::File);
+        sandbox.deny_set(WordSet::Tools);
+        sandbox.allow(".s");
+        sandbox
+    }
+
+    pub fn deny(&mut self, name: &str) {
+        self.denied.insert(name.to_lowercase());
+    }
+
+    pub fn allow(&mut self, name: &str) {
+        self.denied.remove(&name.to_lowercase());
+    }
+
+    pub fn deny_set(&mut self, set: WordSet) {
+        for name in set.names() {
+            self.deny(&name);
+        }
+    }
+
+    pub fn allow_set(&mut self, set: WordSet) {
+        for name in set.names() {
+            self.allow(&name);
+        }
+    }
+
+    /// true if scripts must not use or define `name`
+    pub fn denies(&self, name: &str) -> bool {
+        !self.denied.is_empty() && self.denied.contains(&name.to_lowercase())
+    }
+
+    /// the denied name of the native `word` of a word set, if there is one
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let sandbox = Sandbox::untrusted();
+    /// assert_eq!(sandbox.denied_native(builtins::delete_file), Some("delete-file"));
+    /// assert_eq!(sandbox.denied_native(builtins::dup), None);
+    /// ```
+    pub fn denied_native(&self, word: fn(&mut VM) -> Result<()>) -> Option<&'static str> {
+        if self.denied.is_empty() {
+            return None;
+        }
+        natives()
+            .get(&(word as *const () as usize))?
+            .iter()
+            .find(|name| self.denied.contains(*name))
+            .map(|name| name.as_str())
+    }
+
+    /// true if there is any quota to check
+    pub fn limited(&self) -> bool {
+        self.max_value_stack.is_some()
+            || self.max_return_stack.is_some()
+            || self.max_dictionary.is_some()
+            || self.max_string.is_some()
+            || self.max_array.is_some()
+            || self.max_memory.is_some()
+    }
+
+    /// checks all quotas with `cells` in the dictionary
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let sandbox = Sandbox {
+    ///     max_value_stack: Some(1),
+    ///     max_return_stack: Some(1),
+    ///     max_dictionary: Some(10),
+    ///     max_string: Some(4),
+    ///     max_array: Some(2),
+    ///     ..Sandbox::default()
+    /// };
+    /// let stack = |values: &[Variable]| {
+    ///     let mut stack = Stack::new();
+    ///     values.iter().for_each(|value| stack.push(value.clone()));
+    ///     stack
+    /// };
+    /// let (empty, two) = (stack(&[]), stack(&[Variable::Int(1), Variable::Int(2)]));
+    ///
+    /// assert_eq!(sandbox.check(&empty, &empty, 10), Ok(()));
+    /// assert_eq!(sandbox.check(&two, &empty, 0), Err(Error::ValueStackOverflow));
+    /// assert_eq!(sandbox.check(&empty, &two, 0), Err(Error::ReturnStackFull));
+    /// assert_eq!(sandbox.check(&empty, &empty, 11), Err(Error::DictionaryFull));
+    /// let text = stack(&[Variable::from("far too long")]);
+    /// assert_eq!(sandbox.check(&text, &empty, 0), Err(Error::StringTooLong));
+    /// let array = stack(&[Variable::from(vec![1, 2, 3])]);
+    /// assert_eq!(sandbox.check(&array, &empty, 0), Err(Error::ArrayTooLong));
+    ///
+    /// let sandbox = Sandbox {
+    ///     max_memory: Some(1 << 10),
+    ///     ..Sandbox::default()
+    /// };
+    /// assert_eq!(sandbox.check(&empty, &empty, 10), Ok(()));
+    /// assert_eq!(sandbox.check(&empty, &empty, 1000), Err(Error::OutOfMemory));
+    /// ```
+    pub fn check(&self, value_stack: &Stack, return_stack: &Stack, cells: usize) -> Result<()> {
+        let over = |limit: Option<usize>, size: usize| limit.is_some_and(|limit| size > limit);
+        if over(self.max_value_stack, value_stack.len()) {
+            return Err(Error::ValueStackOverflow);
+        }
+        if over(self.max_return_stack, return_stack.len()) {
+            return Err(Error::ReturnStackFull);
+        }
+        if over(self.max_dictionary, cells) {
+            return Err(Error::DictionaryFull);
+        }
+        let longest_string = value_stack
+            .longest_string()
+            .max(return_stack.longest_string());
+        if over(self.max_string, longest_string) {
+            return Err(Error::StringTooLong);
+        }
+        let longest_array = value_stack
+            .longest_array()
+            .max(return_stack.longest_array());
+        if over(self.max_array, longest_array) {
+            return Err(Error::ArrayTooLong);
+        }
+        if over(self.max_memory, memory(value_stack, return_stack, cells)) {
+            return Err(Error::OutOfMemory);
+        }
+        Ok(())
+    }
+
+    /// checks the string and array quotas for the literals compiled into `code`
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut sandbox = Sandbox::default();
+    /// sandbox.max_string = Some(4);
+    ///
+    /// assert_eq!(sandbox.check_literals(&[Cell::Print("text".into())]), Ok(()));
+    /// let text = Cell::Data(Variable::from("far too long"));
+    /// assert_eq!(sandbox.check_literals(&[text]), Err(Error::StringTooLong));
+    /// ```
+    pub fn check_literals(&self, code: &[Cell]) -> Result<()> {
+        if self.max_string.is_none() && self.max_array.is_none() {
+            return Ok(());
+        }
+        let mut literals = Stack::new();
+        for cell in code {
+            match cell {
+                Cell::Data(value) => literals.push(value.clone()),
+                Cell::Print(text) => literals.push(Variable::from(&text[..])),
+                _ => {}
+            }
+        }
+        let empty = Stack::new();
+        Sandbox {
+            max_string: self.max_string,
+            max_array: self.max_array,
+            ..Sandbox::default()
+        }
+        .check(&literals, &empty, 0)
+    }
+
+    /// checks that a string of `bytes` still fits before a native reads it
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut sandbox = Sandbox::default();
+    /// sandbox.max_string = Some(100);
+    /// let stack = Stack::new();
+    ///
+    /// assert_eq!(sandbox.check_string(&stack, &stack, 0, 100), Ok(()));
+    /// assert_eq!(sandbox.check_string(&stack, &stack, 0, 101), Err(Error::StringTooLong));
+    /// ```
+    pub fn check_string(
+        &self,
+        value_stack: &Stack,
+        return_stack: &Stack,
+        cells: usize,
+        bytes: usize,
+    ) -> Result<()> {
+        let over = |limit: Option<usize>, size: usize| limit.is_some_and(|limit| size > limit);
+        if over(self.max_string, bytes) {
+            return Err(Error::StringTooLong);
+        }
+        let memory = memory(value_stack, return_stack, cells) + size_of::<Variable>();
+        if over(self.max_memory, memory.saturating_add(bytes)) {
+            return Err(Error::OutOfMemory);
+        }
+        Ok(())
+    }
+}
+
+/// bytes held by the stacks and `cells` of the dictionary, roughly
+fn memory(value_stack: &Stack, return_stack: &Stack, cells: usize) -> usize {
+    (value_stack.len() + return_stack.len()) * size_of::<Variable>()
+        + value_stack.bytes()
+        + return_stack.bytes()
+        + cells * size_of::<Cell>()
+}
+
+/// names of the natives of all word sets by their function
+fn natives() -> &'static HashMap<usize, Vec<String>> {
+    static NATIVES: OnceLock<HashMap<usize, Vec<String>>> = OnceLock::new();
+    NATIVES.get_or_init(|| {
+        let mut words = Dictionary::new();
+        for set in [
+            WordSet::Core,
+            WordSet::Facility,
+            WordSet::Tools,
+            WordSet::File,
+            WordSet::Tester,
+        ] {
+            set.add(&mut words);
+        }
+        let mut natives: HashMap<usize, Vec<String>> = HashMap::new();
+        for name in words.names() {
+            if let Ok(code) = words.get(name) {
+                if let [Cell::Exec(word) | Cell::Compiled(word)] = &code[..] {
+                    let names = natives.entry(*word as *const () as usize).or_default();
+                    names.push(name.to_owned());
+                    names.sort();
+                }
+            }
+        }
+        natives
+    })
+}
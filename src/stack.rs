@@ -7,46 +7,135 @@ use crate::{Error, Result, Variable};
 // TODO: investigate Vec or VecDeque
 // TODO: document and doctest
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Stack {
     val: VecDeque<Variable>,
+    /// `heap_size` of all values
+    bytes: usize,
+    /// longest string and array pushed since the last `clear`
+    longest: (usize, usize),
+    /// fewest values since the last `set_lowest`
+    lowest: usize,
 }
 
 impl Stack {
     pub fn new() -> Stack {
-        Stack { val: vec![].into() }
+        Stack::default()
     }
+
+    /// keeps track of the size of `value` before it goes on the stack
+    fn track(&mut self, value: &Variable) {
+        if let Variable::Int(_) = value {
+            return;
+        }
+        self.bytes += value.heap_size();
+        let (string, array) = longest(value);
+        self.longest = (self.longest.0.max(string), self.longest.1.max(array));
+    }
+
+    fn untrack(&mut self, value: &Variable) {
+        self.bytes = self.bytes.saturating_sub(value.heap_size());
+    }
+
     pub fn push<T>(&mut self, value: T)
     where
         T: Into<Variable>,
     {
-        self.val.push_front(value.into());
+        let value = value.into();
+        self.track(&value);
+        self.val.push_front(value);
     }
 
     pub fn pop(&mut self) -> Result<Variable> {
-        if self.val.len() > 0 {
-            return Ok(self.val.pop_front().unwrap());
-        }
-        Err(Error::Stack)
+        let value = self.val.pop_front().ok_or(Error::Stack)?;
+        self.untrack(&value);
+        self.lowest = self.lowest.min(self.val.len());
+        Ok(value)
     }
 
+    /// value `pos` places below the top, `0` is the top
     pub fn at(&self, pos: usize) -> Result<&Variable> {
-        if self.val.len() > 0 {
-            return Ok(self.val.get(pos).unwrap());
-        }
-        Err(Error::Stack)
+        self.val.get(pos).ok_or(Error::Stack)
     }
 
+    /// the size of the value is not tracked, only change `Int`s in place
     pub fn at_mut(&mut self, pos: usize) -> Result<&mut Variable> {
-        if self.val.len() > 0 {
-            return Ok(self.val.get_mut(pos).unwrap());
+        self.val.get_mut(pos).ok_or(Error::Stack)
+    }
+
+    /// puts `value` `pos` places below the top, `0` is a push
+    pub fn insert<T>(&mut self, pos: usize, value: T) -> Result<()>
+    where
+        T: Into<Variable>,
+    {
+        if pos > self.val.len() {
+            return Err(Error::Stack);
         }
-        Err(Error::Stack)
+        let value = value.into();
+        self.track(&value);
+        self.val.insert(pos, value);
+        Ok(())
+    }
+
+    /// takes out the value `pos` places below the top, `0` is a pop
+    pub fn remove(&mut self, pos: usize) -> Result<Variable> {
+        let value = self.val.remove(pos).ok_or(Error::Stack)?;
+        self.untrack(&value);
+        self.lowest = self.lowest.min(self.val.len());
+        Ok(value)
+    }
+
+    /// removes all values
+    pub fn clear(&mut self) {
+        *self = Stack::default();
+    }
+
+    /// bytes the strings and arrays on the stack hold on the heap
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// length of the longest string pushed since the last `clear`,
+    /// strings in arrays included
+    pub fn longest_string(&self) -> usize {
+        self.longest.0
     }
+
+    /// length of the longest array pushed since the last `clear`
+    pub fn longest_array(&self) -> usize {
+        self.longest.1
+    }
+
+    /// fewest values on the stack since the last `set_lowest`
+    ///
+    /// ```
+    /// # use frust::*;
+    /// let mut stack = Stack::new();
+    /// stack.push(1);
+    /// stack.push(2);
+    /// stack.set_lowest(2);
+    ///
+    /// stack.pop();
+    /// stack.push(3);
+    /// assert_eq!((stack.len(), stack.lowest()), (2, 1));
+    /// ```
+    pub fn lowest(&self) -> usize {
+        self.lowest
+    }
+
+    /// starts counting `lowest` from `lowest`, or the length if that's less
+    pub fn set_lowest(&mut self, lowest: usize) {
+        self.lowest = lowest.min(self.val.len());
+    }
+
     pub fn len(&self) -> usize {
         self.val.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.val.is_empty()
+    }
+
     pub fn iter(&'_ self) -> StackIterator<'_> {
         StackIterator {
             stack: self,
@@ -55,6 +144,20 @@ impl Stack {
     }
 }
 
+/// length of the longest string and the longest array in `value`
+fn longest(value: &Variable) -> (usize, usize) {
+    match value {
+        Variable::Int(_) => (0, 0),
+        Variable::String(text) => (text.len(), 0),
+        Variable::Array(values) => values.iter().map(longest).fold(
+            (0, values.len()),
+            |(string, array), (inner_string, inner_array)| {
+                (string.max(inner_string), array.max(inner_array))
+            },
+        ),
+    }
+}
+
 pub struct StackIterator<'a> {
     stack: &'a Stack,
     index: usize,
@@ -97,5 +200,46 @@ mod test {
         }
         let ret = stack.pop();
         assert_eq!(ret, Err(Error::Stack));
+        assert_eq!(stack.at(0), Err(Error::Stack));
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut stack = Stack::new();
+        stack.push(1);
+        stack.push(3);
+
+        stack.insert(1, 2).expect("insert not working");
+        assert_eq!(stack.insert(4, 4), Err(Error::Stack));
+        assert_eq!(stack.at(1), Ok(&Variable::Int(2)));
+        assert_eq!(stack.at(3), Err(Error::Stack));
+
+        assert_eq!(stack.remove(2), Ok(Variable::Int(1)));
+        assert_eq!(stack.remove(2), Err(Error::Stack));
+        assert_eq!(stack.remove(0), Ok(Variable::Int(3)));
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn test_usage() {
+        let mut stack = Stack::new();
+        stack.push("four");
+        stack.push(Variable::from(vec!["a".to_owned(), "bcdefg".to_owned()]));
+        stack.push(1);
+
+        assert_eq!(stack.longest_string(), 6);
+        assert_eq!(stack.longest_array(), 2);
+        let array = Variable::from(vec!["a".to_owned(), "bcdefg".to_owned()]).heap_size();
+        assert_eq!(stack.bytes(), 4 + array);
+
+        stack.pop().unwrap();
+        stack.remove(0).unwrap();
+        assert_eq!(stack.bytes(), 4);
+        // the longest values are remembered until `clear`
+        assert_eq!(stack.longest_string(), 6);
+
+        stack.clear();
+        assert!(stack.is_empty());
+        assert_eq!((stack.bytes(), stack.longest_string()), (0, 0));
     }
 }
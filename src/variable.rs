@@ -3,6 +3,7 @@ use std::fmt::Display;
 /// value on the stack
 /// - `String`: owned string
 /// - `Int`: 64 bit signed integer
+///
 /// TODO: document
 /// TODO: test
 #[derive(Debug, PartialEq, Clone, PartialOrd)]
@@ -11,6 +12,28 @@ pub enum Variable {
     String(String),
     Int(i64),
 }
+impl Variable {
+    /// bytes the value holds on the heap, the length of strings
+    /// and the elements of arrays
+    ///
+    /// ```
+    /// # use frust::*;
+    /// assert_eq!(Variable::Int(42).heap_size(), 0);
+    /// assert_eq!(Variable::from("four").heap_size(), 4);
+    /// let array = Variable::from(vec![1, 2]);
+    /// assert_eq!(array.heap_size(), 2 * std::mem::size_of::<Variable>());
+    /// ```
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Variable::Int(_) => 0,
+            Variable::String(text) => text.len(),
+            Variable::Array(values) => values
+                .iter()
+                .map(|value| std::mem::size_of::<Variable>() + value.heap_size())
+                .sum(),
+        }
+    }
+}
 impl From<i64> for Variable {
     fn from(value: i64) -> Self {
         Variable::Int(value)
@@ -51,66 +74,68 @@ impl Display for Variable {
         match self {
             Self::Int(v) => write!(f, "{}", v), // TODO evaluate value mode
             Self::String(v) => write!(f, "{}", v),
-            Self::Array(values) => values.iter().map(|v| write!(f, "{}", v)).collect(),
+            Self::Array(values) => values.iter().try_for_each(|v| write!(f, "{}", v)),
         }
     }
 }
+/// arithmetic on `Int` wraps around like on a two's complement machine,
+/// anything else, including a division by zero, is `NAN`
 impl std::ops::Add for Variable {
     type Output = Variable;
 
     fn add(self, rhs: Variable) -> Self::Output {
-        return match (self, rhs) {
-            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a + b),
+        match (self, rhs) {
+            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a.wrapping_add(b)),
             _ => Variable::String("NAN".into()),
-        };
+        }
     }
 }
 impl std::ops::Sub for Variable {
     type Output = Variable;
 
     fn sub(self, rhs: Variable) -> Self::Output {
-        return match (self, rhs) {
-            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a - b),
+        match (self, rhs) {
+            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a.wrapping_sub(b)),
             _ => Variable::String("NAN".into()),
-        };
+        }
     }
 }
 impl std::ops::Mul for Variable {
     type Output = Variable;
 
     fn mul(self, rhs: Variable) -> Self::Output {
-        return match (self, rhs) {
-            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a * b),
+        match (self, rhs) {
+            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a.wrapping_mul(b)),
             _ => Variable::String("NAN".into()),
-        };
+        }
     }
 }
 impl std::ops::Div for Variable {
     type Output = Variable;
 
     fn div(self, rhs: Variable) -> Self::Output {
-        return match (self, rhs) {
-            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a / b),
+        match (self, rhs) {
+            (Variable::Int(a), Variable::Int(b)) if b != 0 => Variable::Int(a.wrapping_div(b)),
             _ => Variable::String("NAN".into()),
-        };
+        }
     }
 }
 impl std::ops::Rem for Variable {
     type Output = Variable;
 
     fn rem(self, rhs: Variable) -> Self::Output {
-        return match (self, rhs) {
-            (Variable::Int(a), Variable::Int(b)) => Variable::Int(a % b),
+        match (self, rhs) {
+            (Variable::Int(a), Variable::Int(b)) if b != 0 => Variable::Int(a.wrapping_rem(b)),
             _ => Variable::String("NAN".into()),
-        };
+        }
     }
 }
-impl Into<i64> for Variable {
-    fn into(self) -> i64 {
-        match self {
+impl From<Variable> for i64 {
+    fn from(value: Variable) -> Self {
+        match value {
             Variable::Array(variables) => variables.len() as i64,
             Variable::String(value) => value.len() as i64,
             Variable::Int(value) => value,
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,92 @@
+//! VMs sharing a read only base dictionary
+
+use frust::*;
+use std::{sync::Arc, thread};
+
+mod common;
+
+fn base() -> Arc<Dictionary> {
+    let mut vm = common::vm(&Capture::new());
+    vm.eval(": sq dup * ; : sum-sq 0 swap 1 + 1 do i sq + loop ;")
+        .unwrap();
+    Arc::new(std::mem::take(&mut vm.dictionary))
+}
+
+fn vm(base: &Arc<Dictionary>, output: &Capture) -> VM {
+    let mut vm = common::vm(output);
+    vm.dictionary = Dictionary::with_base(base.clone());
+    vm
+}
+
+#[test]
+fn own_words_shadow_the_base_without_changing_it() {
+    let base = base();
+    let output = Capture::new();
+    let mut vm = vm(&base, &output);
+
+    vm.eval(": sq drop 1 ; 3 sq . 3 sum-sq . : double 2 * ; 4 double .")
+        .unwrap();
+    vm.flush().unwrap();
+    // `sum-sq` was bound to the `sq` of the base when it was compiled
+    assert_eq!(output.take(), "Warning: redefined sq\n1 14 8 ");
+
+    let mut other = self::vm(&base, &output);
+    other.handle_errors = false;
+    other.eval("3 sq . double").unwrap_err();
+    other.flush().unwrap();
+    assert_eq!(output.take(), "9 ");
+    assert!(base.get("double").is_err());
+}
+
+#[test]
+fn the_base_is_shared_across_threads() {
+    let mut base = Arc::into_inner(base()).unwrap();
+    let mut calls = 0;
+    base.add_closure("calls", move |vm| {
+        calls += 1;
+        vm.value_stack.push(calls);
+        Ok(())
+    });
+    let base = Arc::new(base);
+    let workers: Vec<_> = (1..=4)
+        .map(|n| {
+            let output = Capture::new();
+            let mut vm = vm(&base, &output);
+            let worker = thread::spawn(move || {
+                vm.eval(&format!(
+                    ": f {} sum-sq 100 0 do calls drop loop ; f .",
+                    n * 10
+                ))
+                .unwrap();
+                vm.flush().unwrap();
+            });
+            (n, output, worker)
+        })
+        .collect();
+
+    for (n, output, worker) in workers {
+        worker.join().unwrap();
+        let count = n * 10;
+        let expected = count * (count + 1) * (2 * count + 1) / 6;
+        assert_eq!(output.take(), format!("{} ", expected));
+    }
+    assert!(base.get("f").is_err());
+    let mut vm = vm(&base, &Capture::new());
+    assert_eq!(vm.call::<(), i64>("calls", ()), Ok(401));
+}
+
+#[test]
+fn quotas_count_the_own_words_only() {
+    let base = base();
+    let mut vm = vm(&base, &Capture::new());
+    vm.handle_errors = false;
+    vm.sandbox.max_dictionary = Some(8);
+    assert!(base.cells() > 8);
+
+    assert_eq!(vm.eval(": sq dup * ;"), Ok(()));
+    assert_eq!(
+        vm.eval(": long 1 2 3 4 5 6 7 8 ;"),
+        Err(Error::DictionaryFull)
+    );
+    assert!(vm.dictionary.cells() <= 8);
+}
@@ -0,0 +1,37 @@
+//! typed calls from rust with `VM::call`
+
+use frust::*;
+
+mod common;
+
+#[test]
+fn the_values_of_the_caller_stay() {
+    let mut vm = common::vm(&Capture::new());
+    vm.handle_errors = false;
+    vm.eval(": same ; : eat drop drop 1 ; : inner 2 * ; : outer drop 3 inner ;")
+        .unwrap();
+    vm.value_stack.push(42);
+
+    assert_eq!(
+        vm.call::<_, (i64, i64, i64)>("dup", 1),
+        Err(Error::Arity(3, 2))
+    );
+    assert_eq!(vm.call::<_, u8>("negate", 1), Err(Error::Type));
+    assert_eq!(vm.call::<_, i64>("/", (1, 0)), Err(Error::DivisionByZero));
+    assert_eq!(
+        vm.call::<_, i64>("if", 1),
+        Err(Error::Parser("Interpreting a compile-only word".to_owned()))
+    );
+    assert_eq!(vm.call::<_, String>("same", "text"), Ok("text".to_owned()));
+    assert_eq!(vm.value_stack.len(), 1);
+
+    // the word takes values of the caller
+    assert_eq!(vm.call::<_, i64>("eat", 1), Err(Error::Arity(1, 2)));
+    assert!(vm.value_stack.is_empty());
+    vm.value_stack.push(42);
+    vm.value_stack.push(5);
+    // the depth is right but `5` was taken from the caller
+    assert_eq!(vm.call::<_, i64>("inner", 4), Ok(8));
+    assert_eq!(vm.call::<_, i64>("outer", ()), Err(Error::Arity(0, 1)));
+    assert_eq!(vm.value_stack.pop(), Ok(Variable::Int(42)));
+}
@@ -0,0 +1,115 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn frust(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_frust"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("frust did not start");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn evaluate_expressions_in_order() {
+    let output = frust(&["-e", ": sq dup * ;", "-e", "7 sq ."], "");
+    assert_eq!(stdout(&output), "49 ");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn run_script_file() {
+    let output = frust(&["-q", "tests/99bottles.fr"], "");
+    assert!(stdout(&output).starts_with("\n99 bottles of beer on the wall\n"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn error_stops_script_with_failure() {
+    let output = frust(&["-e", "1 . nope 2 ."], "");
+    assert_eq!(stdout(&output), "1 ");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nope"));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn unterminated_definitions_fail() {
+    let output = frust(&["-e", ": foo 1 2"], "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unterminated definition"));
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = frust(&["-q"], ": foo 1\n2\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unterminated definition"));
+    assert_eq!(output.status.code(), Some(1));
+
+    // a definition may go on in the next expression
+    let output = frust(&["-e", ": foo 1", "-e", "2 ; foo . ."], "");
+    assert_eq!(stdout(&output), "2 1 ");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn bye_sets_exit_code() {
+    let output = frust(&["-e", "3 bye", "-e", "1 ."], "");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = frust(&["-q"], "1 .\nbye\n2 .\n");
+    assert_eq!(stdout(&output), "1 ");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn interactive_after_expressions() {
+    let output = frust(&["-q", "-e", ": five 5 ;", "-i"], "five .\n");
+    assert_eq!(stdout(&output), "5 ");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn repl_errors_give_failure() {
+    let output = frust(&["-q"], "1 .\nnope\n2 .\n");
+    assert_eq!(stdout(&output), "1 2 ");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn invalid_arguments() {
+    let output = frust(&["--nope"], "");
+    assert_eq!(output.status.code(), Some(2));
+    let output = frust(&["-e"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn images_keep_a_session() {
+    let path = std::env::temp_dir().join("frust-cli-image.img");
+    let path = path.display().to_string();
+    let save = format!(": sq dup * ; 3 s\" {}\" save-image", path);
+    let output = frust(&["-e", &save], "");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = frust(&["--image", &path, "-e", "sq ."], "");
+    assert_eq!(stdout(&output), "9 ");
+    assert_eq!(output.status.code(), Some(0));
+    std::fs::remove_file(&path).unwrap();
+
+    let output = frust(&["--image", &path], "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("frust-cli-image.img"));
+    assert_eq!(output.status.code(), Some(1));
+}
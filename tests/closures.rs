@@ -0,0 +1,39 @@
+//! native words with captured state
+
+use frust::*;
+use std::sync::{Arc, Mutex};
+
+mod common;
+
+#[test]
+fn closures_share_state_with_the_host() {
+    let mut vm = common::vm(&Capture::new());
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let captured = log.clone();
+    vm.dictionary.add_closure("log", move |vm| {
+        captured.lock().unwrap().push(vm.value_stack.pop()?);
+        Ok(())
+    });
+
+    vm.eval(": twice dup log log ; 1 log 2 twice").unwrap();
+    assert_eq!(
+        *log.lock().unwrap(),
+        [Variable::Int(1), Variable::Int(2), Variable::Int(2)]
+    );
+    assert!(vm.value_stack.is_empty());
+    let code = vm.dictionary.get("log").unwrap();
+    assert_eq!(vm.dictionary.name_of(&code[0]), Some("log"));
+}
+
+#[test]
+fn closures_are_profiled_by_name() {
+    let mut vm = common::vm(&Capture::new());
+    vm.dictionary.add_closure("work", |_| Ok(()));
+    vm.eval(": f work work ;").unwrap();
+
+    vm.profiler.enabled = true;
+    vm.eval("f").unwrap();
+    let report = vm.profiler.report(&vm.dictionary);
+    let work = report.iter().find(|word| word.name == "work").unwrap();
+    assert_eq!(work.calls, 2);
+}
@@ -0,0 +1,11 @@
+//! what the integration tests share
+
+use frust::*;
+
+/// a VM with all word sets, output and diagnostics go to `output`
+pub fn vm(output: &Capture) -> VM {
+    let mut vm = VM::new(std::io::empty(), output.clone());
+    vm.diagnostics = Box::new(output.clone());
+    builtins::add_all(&mut vm.dictionary);
+    vm
+}
@@ -0,0 +1,99 @@
+//! stepping through definitions with `DEBUG`, commands come from the input
+
+use frust::*;
+
+fn debug(commands: &str, program: &str) -> String {
+    let output = Capture::new();
+    let mut vm = VM::new(std::io::Cursor::new(commands.to_owned()), output.clone());
+    builtins::add_all(&mut vm.dictionary);
+    vm.eval(program).unwrap();
+    output.take()
+}
+
+#[test]
+fn step_into_and_out() {
+    let screen = debug(
+        "s\ns\ns\no\nn\n",
+        ": sq dup * ; : f 1 + sq 1- ; debug f 2 f .",
+    );
+    assert_eq!(
+        screen,
+        "\
+f: [1 +] sq 1-
+  stack <1> 2
+debug> f: 1 + [sq] 1-
+  stack <1> 3
+debug> sq: [dup] *
+  stack <1> 3
+debug> sq: dup [*]
+  stack <2> 3 3
+debug> f: 1 + sq [1-]
+  stack <1> 9
+debug> 8 "
+    );
+}
+
+#[test]
+fn continue_to_the_next_breakpoint() {
+    let screen = debug(
+        "c\nc\nq\n",
+        ": down ?dup if 1- recurse then ; debug down 5 down 1 down",
+    );
+    assert_eq!(screen.matches("debug> ").count(), 3);
+    assert!(screen.starts_with("down: [?dup] 0branch>3 1- recurse\n  stack <1> 5\n"));
+    assert!(screen.contains("  stack <1> 4\n"));
+}
+
+#[test]
+fn breakpoints_in_inlined_words() {
+    // `f` is compiled before the breakpoint, with `sq` copied into it
+    let screen = debug("c\n", ": sq dup * ; : f 3 sq . ; debug sq f");
+    assert_eq!(screen, "sq: [dup] *\n  stack <1> 3\ndebug> 9 ");
+}
+
+#[test]
+fn loops_show_the_return_stack() {
+    let screen = debug("n\nn\nn\nc\n", ": f 3 0 do i drop loop ; debug f f");
+    assert!(screen.contains("f: 3 0 do [i] drop loop>-2\n  stack <0>\n  return <2> 3 0\n"));
+}
+
+#[test]
+fn unknown_commands_show_the_help() {
+    let screen = debug("?\nc\n", ": f 1 drop ; debug f f");
+    assert!(screen.contains(builtins::DEBUG_HELP));
+    assert_eq!(screen.matches("debug> ").count(), 2);
+}
+
+#[test]
+fn end_of_input_runs_on() {
+    assert_eq!(
+        debug("", ": f 1 . ; debug f f f"),
+        "f: [1] .\n  stack <0>\ndebug> 1 f: [1] .\n  stack <0>\ndebug> 1 "
+    );
+}
+
+#[test]
+fn unknown_words_are_an_error() {
+    let mut vm = VM::new_null();
+    builtins::add_all(&mut vm.dictionary);
+    vm.handle_errors = false;
+    assert_eq!(
+        vm.eval("debug nope"),
+        Err(Error::Unimplemented("nope".to_owned()))
+    );
+}
+
+#[test]
+fn from_the_repl() {
+    let output = Capture::new();
+    let input = ": sq dup * ;\ndebug sq\n3 sq .\nn\nn\n2 sq .\n";
+    let mut vm = VM::new(input.as_bytes(), output.clone());
+    builtins::add_all(&mut vm.dictionary);
+    let mut repl = repl::Repl::new(repl::Editor::new(repl::History::new()));
+    repl.run(&mut vm).unwrap();
+
+    let screen = output.take().replace("\x1b[K", "");
+    assert!(screen.contains("> 3 sq . sq: [dup] *\n  stack <1> 3\ndebug> "));
+    assert!(screen.contains("debug> 9  ok\n"));
+    assert!(screen.contains("> 2 sq . sq: [dup] *\n"));
+}
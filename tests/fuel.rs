@@ -0,0 +1,66 @@
+//! time slicing with `VM::fuel`, `Error::Yield` and `VM::resume`
+
+use frust::*;
+
+mod common;
+
+#[test]
+fn definitions_and_the_rest_of_the_line_go_on() {
+    let output = Capture::new();
+    let mut vm = common::vm(&output);
+    vm.fuel = Some(10);
+    assert_eq!(
+        vm.eval(": f 100 0 do loop ; f 1 .\n: g 2 . ; g"),
+        Err(Error::Yield)
+    );
+    vm.fuel = None;
+    assert_eq!(vm.resume(), Ok(()));
+    assert_eq!(output.take(), "1 2 ");
+    assert!(!vm.suspended());
+}
+
+#[test]
+fn errors_after_resume_are_handled() {
+    let output = Capture::new();
+    let mut vm = common::vm(&output);
+    vm.fuel = Some(5);
+    assert_eq!(vm.eval(": f 10 0 do loop drop ; f 7 ."), Err(Error::Yield));
+    vm.fuel = None;
+    assert_eq!(vm.resume(), Ok(()));
+    assert_eq!(output.take(), "Error: Stack\n7 ");
+
+    vm.handle_errors = false;
+    vm.fuel = Some(5);
+    assert_eq!(vm.eval("f 7 ."), Err(Error::Yield));
+    vm.fuel = None;
+    assert_eq!(vm.resume(), Err(Error::Stack));
+    assert!(!vm.suspended());
+}
+
+#[test]
+fn words_run_by_natives_cannot_yield() {
+    let output = Capture::new();
+    let mut vm = common::vm(&output);
+    vm.handle_errors = false;
+    vm.eval(": f 100 0 do loop ;").unwrap();
+    vm.fuel = Some(50);
+    assert_eq!(vm.eval("profile f"), Err(Error::OutOfFuel));
+    assert!(!vm.suspended());
+}
+
+#[test]
+fn included_files_cannot_yield() {
+    let output = Capture::new();
+    let mut vm = common::vm(&output);
+    vm.fuel = Some(50);
+    let result = vm.include_source("loop.fr", ": f 100 0 do loop ;\nf\n1 .");
+    assert_eq!(
+        result,
+        Err(Error::Included(
+            "loop.fr".to_owned(),
+            2,
+            Box::new(Error::OutOfFuel)
+        ))
+    );
+    assert!(!vm.suspended());
+}
@@ -0,0 +1,222 @@
+//! `VM::eval` never panics
+//!
+//! random token streams are fed into a VM with every word set loaded.
+//! the streams are built from the words of the dictionary, control words,
+//! string words and numbers, including the edges of the `i64` range.
+//!
+//! the cases are generated from a fixed seed so a failure is reproducible,
+//! the seed and the number of cases can be changed for longer runs:
+//!
+//! ```sh
+//! FRUST_FUZZ_SEED=7 FRUST_FUZZ_CASES=100000 cargo test --test fuzz
+//! ```
+//!
+//! every program gets a few slices of `fuel`, programs that loop forever
+//! are cancelled after that.
+
+use frust::*;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// words that reach out of the VM, into the file system or the process
+const DENIED: &[&str] = &[
+    "open-file",
+    "create-file",
+    "close-file",
+    "read-line",
+    "read-file",
+    "write-line",
+    "write-file",
+    "flush-file",
+    "file-size",
+    "file-position",
+    "reposition-file",
+    "delete-file",
+    "rename-file",
+    "file-status",
+    "include-file",
+    "included",
+    "include",
+    "required",
+    "require",
+    "save-image",
+    "load-image",
+];
+
+/// tokens that aren't words on their own but shape the stream
+const SYNTAX: &[&str] = &[
+    ":", ";", "if", "else", "then", "loop", "+loop", "-loop", "(", ")", "\\", ".\"", "s\"", "x\"",
+    "\"", "[char]",
+];
+
+/// cells per slice and slices per program
+const FUEL: u64 = 10_000;
+const SLICES: usize = 4;
+
+/// xorshift64*, good enough to pick tokens
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+fn env(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn new_vm() -> VM {
+    let mut vm = VM::new(std::io::empty(), Capture::new());
+    vm.diagnostics = Box::new(Capture::new());
+    builtins::add_all(&mut vm.dictionary);
+    builtins::add_tester(&mut vm.dictionary);
+    vm
+}
+
+/// all words of a fresh VM that may show up in a stream
+fn words() -> Vec<&'static str> {
+    let vm = new_vm();
+    let mut words: Vec<&'static str> = vm
+        .dictionary
+        .names()
+        .filter(|word| !DENIED.contains(word))
+        .map(|word| &*Box::leak(word.to_owned().into_boxed_str()))
+        .collect();
+    words.sort();
+    words.extend(SYNTAX);
+    words
+}
+
+/// a program of a few lines of random tokens
+fn program(rng: &mut Rng, words: &[&str], numbers: &[&str], extra: &[&str]) -> String {
+    let mut program = String::new();
+    for _ in 0..1 + rng.below(3) {
+        for _ in 0..rng.below(24) {
+            let token = match rng.below(10) {
+                0..=2 => rng.pick(numbers),
+                3 if !extra.is_empty() => rng.pick(extra),
+                _ => rng.pick(words),
+            };
+            program.push_str(token);
+            program.push(' ');
+        }
+        program.push('\n');
+    }
+    program
+}
+
+/// runs a program slice by slice, cancels it if it doesn't end
+fn run(vm: &mut VM, source: &str) {
+    vm.fuel = Some(FUEL);
+    let mut result = vm.eval(source);
+    for _ in 1..SLICES {
+        if result != Err(Error::Yield) {
+            return;
+        }
+        vm.fuel = Some(FUEL);
+        result = vm.resume();
+    }
+    vm.cancel();
+}
+
+/// runs every program in its own VM and fails with the program that panicked
+fn check(name: &str, cases: u64, mut generate: impl FnMut(&mut Rng) -> String, setup: fn(&mut VM)) {
+    let seed = env("FRUST_FUZZ_SEED", 0x5eed);
+    let cases = env("FRUST_FUZZ_CASES", cases);
+    let mut rng = Rng(seed.max(1));
+    for case in 0..cases {
+        let source = generate(&mut rng);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut vm = new_vm();
+            setup(&mut vm);
+            run(&mut vm, &source);
+        }));
+        assert!(
+            result.is_ok(),
+            "{} case {} (seed {}) panicked on:\n{}",
+            name,
+            case,
+            seed,
+            source
+        );
+    }
+}
+
+#[test]
+fn eval_never_panics() {
+    let words = words();
+    let numbers = [
+        "0",
+        "1",
+        "-1",
+        "2",
+        "3",
+        "9223372036854775807",
+        "-9223372036854775808",
+        "4611686018427387904",
+        "-4611686018427387905",
+        "99999999999999999999",
+    ];
+    check(
+        "eval_never_panics",
+        3000,
+        |rng| program(rng, &words, &numbers, &[]),
+        |_| {},
+    );
+}
+
+#[test]
+fn control_flow_never_panics() {
+    let words = words();
+    let numbers = ["0", "1", "2", "3", "-1", "-2"];
+    let extra = [
+        "do", "loop", "+loop", "i", "j", "recurse", ":", ";", "if", "then",
+    ];
+    check(
+        "control_flow_never_panics",
+        3000,
+        |rng| program(rng, &words, &numbers, &extra),
+        |_| {},
+    );
+    check(
+        "control_flow_never_panics without tail calls",
+        1000,
+        |rng| program(rng, &words, &numbers, &extra),
+        |vm| {
+            vm.tail_calls = false;
+            vm.max_call_depth = 64;
+        },
+    );
+}
+
+#[test]
+fn deep_recursion_errors() {
+    let mut vm = new_vm();
+    vm.handle_errors = false;
+    vm.tail_calls = false;
+    assert_eq!(vm.eval(": f recurse ; f"), Err(Error::ReturnStackOverflow));
+}
+
+#[test]
+fn endless_loops_yield() {
+    let mut vm = new_vm();
+    vm.fuel = Some(FUEL);
+    assert_eq!(vm.eval(": f recurse ; f"), Err(Error::Yield));
+    vm.cancel();
+    vm.fuel = Some(FUEL);
+    assert_eq!(vm.eval(": g 1 0 do 0 +loop ; g"), Err(Error::Yield));
+}
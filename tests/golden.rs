@@ -0,0 +1,164 @@
+//! golden file tests
+//!
+//! every `*.fr` below `tests/` is run in a fresh `VM` and everything it writes,
+//! output and diagnostics, is compared to the `*.expected` file next to it.
+//!
+//! scripts are evaluated line by line, an error drops the rest of its line
+//! and the script goes on with the next one.
+//! the tester words `T{ -> }T` are loaded, scripts that use them get
+//! a table of passed and failed tests per word appended.
+//! failing tests that are recorded in the expectation are known failures,
+//! fixing one changes the table and needs a bless.
+//!
+//! run with `FRUST_BLESS=1` to write the current output as the new expectation:
+//!
+//! ```sh
+//! FRUST_BLESS=1 cargo test --test golden
+//! ```
+
+use frust::*;
+use std::path::{Path, PathBuf};
+
+fn find_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).expect("tests directory") {
+        let path = entry.expect("directory entry").path();
+        if path.is_dir() {
+            find_scripts(&path, scripts);
+        } else if path.extension().is_some_and(|ext| ext == "fr") {
+            scripts.push(path);
+        }
+    }
+}
+
+fn scripts() -> Vec<PathBuf> {
+    let mut scripts = Vec::new();
+    find_scripts(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests"),
+        &mut scripts,
+    );
+    scripts.sort();
+    scripts
+}
+
+fn run(script: &Path) -> String {
+    let output = Capture::new();
+    let mut vm = VM::new(std::io::empty(), output.clone());
+    vm.diagnostics = Box::new(output.clone());
+    builtins::add_all(&mut vm.dictionary);
+    builtins::add_tester(&mut vm.dictionary);
+
+    let source = std::fs::read_to_string(script).expect("read script");
+    vm.files.including.push(script.canonicalize().unwrap());
+    vm.handle_errors = false;
+    let name = script.display().to_string();
+    for (number, line) in source.lines().enumerate() {
+        match vm.eval(line) {
+            Ok(()) => {}
+            Err(Error::Bye(_)) => break,
+            Err(error) => {
+                let error = Error::Included(name.clone(), number + 1, Box::new(error));
+                vm.diagnose(Severity::Error, &error.to_string());
+                vm.input_buffer.clear();
+                vm.state = State::Interpret;
+            }
+        }
+    }
+    vm.tester.close();
+    if !vm.tester.counts.is_empty() {
+        let report = vm.tester.report();
+        let _ = vm.write(&report);
+    }
+    let _ = vm.flush();
+    // paths differ between machines
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .canonicalize()
+        .unwrap();
+    output.take().replace(&format!("{}/", dir.display()), "")
+}
+
+/// line based diff, `-` expected and `+` actual lines
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // longest common subsequence table
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // (marker, line number, text)
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            ops.push((' ', i + 1, expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', i + 1, expected[i]));
+            i += 1;
+        } else {
+            ops.push(('+', j + 1, actual[j]));
+            j += 1;
+        }
+    }
+
+    // only show changes with a few lines of context
+    const CONTEXT: usize = 2;
+    let changed: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != ' ').collect();
+    let mut out = String::new();
+    let mut last = None;
+    for (k, (marker, number, text)) in ops.iter().enumerate() {
+        let near = changed
+            .iter()
+            .any(|&change| k + CONTEXT >= change && k <= change + CONTEXT);
+        if !near {
+            continue;
+        }
+        if last.is_some_and(|last| last + 1 != k) {
+            out.push_str("  ...\n");
+        }
+        out.push_str(&format!("{} {:4} | {}\n", marker, number, text));
+        last = Some(k);
+    }
+    out
+}
+
+#[test]
+fn golden() {
+    let bless = std::env::var_os("FRUST_BLESS").is_some();
+    let mut failures = Vec::new();
+
+    for script in scripts() {
+        let expected_file = script.with_extension("expected");
+        let actual = run(&script);
+
+        if bless {
+            std::fs::write(&expected_file, &actual).expect("write expectation");
+            continue;
+        }
+        match std::fs::read_to_string(&expected_file) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{} differs from {} (- expected, + actual):\n{}",
+                script.display(),
+                expected_file.display(),
+                diff(&expected, &actual)
+            )),
+            Err(_) => failures.push(format!(
+                "{} has no {}, run with FRUST_BLESS=1 to create it",
+                script.display(),
+                expected_file.display()
+            )),
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
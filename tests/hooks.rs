@@ -0,0 +1,118 @@
+//! events of the inner interpreter as `Hooks` see them
+
+use frust::*;
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(Default, Clone)]
+struct Events(Rc<RefCell<Vec<String>>>);
+
+impl Events {
+    fn push(&self, event: &str, step: &Step) {
+        self.0.borrow_mut().push(format!(
+            "{} {}:{}@{}",
+            event, step.word, step.pc, step.depth
+        ));
+    }
+}
+
+impl Hooks for Events {
+    fn enter(&mut self, step: &Step) {
+        self.push("enter", step);
+    }
+    fn after(&mut self, step: &Step) {
+        if matches!(
+            step.cell(),
+            Some(Cell::Call(..) | Cell::Inlined(..) | Cell::Recurse)
+        ) {
+            self.push("after", step);
+        }
+    }
+    fn exit(&mut self, step: &Step) {
+        self.push("exit", step);
+    }
+    fn error(&mut self, step: &Step, error: &Error) {
+        self.push(&format!("error {}", error), step);
+    }
+}
+
+fn run(definitions: &str, line: &str, tail_calls: bool) -> Vec<String> {
+    let mut vm = VM::new_null();
+    builtins::add_core(&mut vm.dictionary);
+    vm.tail_calls = tail_calls;
+    vm.eval(definitions).unwrap();
+
+    let events = Events::default();
+    vm.hooks = Some(Box::new(Local::new(events.clone())));
+    let _ = vm.eval(line);
+    events.0.take()
+}
+
+#[test]
+fn calls_return_to_the_caller() {
+    let definitions = ": inner 1 drop ; : outer inner 2 drop ;";
+    assert_eq!(
+        run(definitions, "outer", true),
+        [
+            "enter outer:0@1",
+            "enter inner:0@2",
+            "exit inner:2@2",
+            "after outer:0@1",
+            // past the copy of `inner` the optimizer left in `outer`
+            "exit outer:5@1",
+        ]
+    );
+}
+
+#[test]
+fn tail_calls_leave_the_caller_first() {
+    let definitions = ": inner 1 drop ; : middle 0 drop inner ; : outer middle 2 drop ;";
+    assert_eq!(
+        run(definitions, "outer", true),
+        [
+            "enter outer:0@1",
+            "enter middle:0@2",
+            "exit middle:2@2",
+            "enter inner:0@2",
+            "exit inner:2@2",
+            "after outer:0@1",
+            "exit outer:3@1",
+        ]
+    );
+    assert_eq!(
+        run(definitions, "outer", false),
+        [
+            "enter outer:0@1",
+            "enter middle:0@2",
+            "enter inner:0@3",
+            "exit inner:2@3",
+            "after middle:2@2",
+            "exit middle:5@2",
+            "after outer:0@1",
+            "exit outer:3@1",
+        ]
+    );
+}
+
+#[test]
+fn recursion_keeps_the_name() {
+    let definitions = ": down ?dup if 1- recurse then ;";
+    let events = run(definitions, "2 down", false);
+    assert_eq!(
+        events[..3],
+        ["enter down:0@1", "enter down:0@2", "enter down:0@3"]
+    );
+}
+
+#[test]
+fn errors_are_reported_for_every_word_left() {
+    let definitions = ": inner 0 drop drop ; : outer inner 1 ;";
+    assert_eq!(
+        run(definitions, "outer", true),
+        [
+            "enter outer:0@1",
+            "enter inner:0@2",
+            "error Stack inner:2@2",
+            "error Stack outer:0@1",
+        ]
+    );
+}
@@ -0,0 +1,134 @@
+//! saving and loading images of the VM
+
+use frust::*;
+
+mod common;
+
+fn save(vm: &VM) -> Vec<u8> {
+    let mut image = Vec::new();
+    save_image(vm, &mut image).unwrap();
+    image
+}
+
+const LIBRARY: &str = "
+: greet .\" hello \" ;
+: stars 0 do 42 emit loop ;
+: countdown dup . 1- ?dup if recurse then ;
+: old 1 ;
+: uses-old old old + ;
+: old 2 ;
+";
+
+#[test]
+fn loaded_images_behave_the_same() {
+    let output = Capture::new();
+    let mut vm = common::vm(&output);
+    vm.optimize = false;
+    vm.max_call_depth = 1000;
+    vm.eval(LIBRARY).unwrap();
+    assert_eq!(output.take(), "Warning: redefined old\n");
+    vm.value_stack.push(7);
+    vm.value_stack.push("text");
+    vm.value_stack.push(vec![1, 2, 3]);
+    let image = save(&vm);
+
+    let mut loaded = VM::new(std::io::empty(), output.clone());
+    load_image(&mut loaded, &mut &image[..]).unwrap();
+    assert!(!loaded.optimize);
+    assert_eq!(loaded.max_call_depth, 1000);
+    assert_eq!(loaded.value_stack.pop(), Ok(Variable::from(vec![1, 2, 3])));
+    assert_eq!(loaded.value_stack.pop(), Ok(Variable::from("text")));
+    assert_eq!(loaded.value_stack.pop(), Ok(Variable::Int(7)));
+
+    let line = "greet 3 stars 3 countdown uses-old . old .";
+    vm.value_stack.clear();
+    vm.eval(line).unwrap();
+    let expected = output.take();
+    loaded.eval(line).unwrap();
+    assert_eq!(output.take(), expected);
+    assert_eq!(expected, "hello***3 2 1 2 2 ");
+
+    // the same dictionary gives the same image
+    loaded.value_stack.push(7);
+    loaded.value_stack.push("text");
+    loaded.value_stack.push(vec![1, 2, 3]);
+    vm.value_stack.push(7);
+    vm.value_stack.push("text");
+    vm.value_stack.push(vec![1, 2, 3]);
+    assert_eq!(save(&loaded), save(&vm));
+    assert_eq!(save(&vm), image);
+}
+
+#[test]
+fn inlined_words_stay_visible() {
+    let mut vm = common::vm(&Capture::new());
+    vm.eval(": sq dup * ; : f 3 sq ;").unwrap();
+    let image = save(&vm);
+
+    let mut loaded = VM::new_null();
+    load_image(&mut loaded, &mut &image[..]).unwrap();
+    assert_eq!(loaded.dictionary.get("f"), vm.dictionary.get("f"));
+    loaded.profiler.enabled = true;
+    loaded.eval("f").unwrap();
+    let report = loaded.profiler.report(&loaded.dictionary);
+    assert!(report
+        .iter()
+        .any(|word| word.name == "sq" && word.calls == 1));
+    assert_eq!(loaded.value_stack.pop(), Ok(Variable::Int(9)));
+}
+
+#[test]
+fn images_keep_to_the_sandbox() {
+    let mut vm = common::vm(&Capture::new());
+    vm.eval(LIBRARY).unwrap();
+    vm.eval(": leave 3 bye ;").unwrap();
+    let image = save(&vm);
+
+    let mut loaded = VM::new_null();
+    loaded.sandbox.deny("bye");
+    assert_eq!(
+        load_image(&mut loaded, &mut &image[..]),
+        Err(Error::Denied("bye".to_owned()))
+    );
+    assert!(loaded.dictionary.get("leave").is_err());
+
+    let mut loaded = VM::new_null();
+    loaded.sandbox.max_dictionary = Some(8);
+    assert_eq!(
+        load_image(&mut loaded, &mut &image[..]),
+        Err(Error::DictionaryFull)
+    );
+    assert!(loaded.dictionary.get("leave").is_err());
+
+    let mut loaded = VM::new_null();
+    loaded.sandbox.max_value_stack = Some(1);
+    vm.eval("1 2").unwrap();
+    assert_eq!(
+        load_image(&mut loaded, &mut &save(&vm)[..]),
+        Err(Error::ValueStackOverflow)
+    );
+    assert!(loaded.value_stack.is_empty());
+}
+
+#[test]
+fn broken_images_are_errors() {
+    let output = Capture::new();
+    let mut vm = common::vm(&output);
+    vm.eval(LIBRARY).unwrap();
+    vm.value_stack
+        .push(vec![vec![1], vec![2]].into_variable().unwrap());
+    let image = save(&vm);
+
+    for len in 0..image.len() {
+        assert!(matches!(
+            load_image(&mut vm, &mut &image[..len]),
+            Err(Error::Image(_))
+        ));
+    }
+    // every byte flipped loads or fails, it never panics
+    for index in 12..image.len() {
+        let mut other = image.clone();
+        other[index] ^= 0xff;
+        let _ = load_image(&mut VM::new_null(), &mut &other[..]);
+    }
+}
@@ -0,0 +1,122 @@
+//! optimized definitions behave like unoptimized ones
+//!
+//! every program runs in two fresh VMs, one with and one without the
+//! optimizer, and both must produce the same output, stack and errors.
+
+use frust::*;
+use std::path::Path;
+
+/// output, stack and result of every line
+fn run(lines: &[&str], optimize: bool) -> (String, Vec<Variable>, Vec<Result<()>>) {
+    let output = Capture::new();
+    let mut vm = VM::new(std::io::empty(), output.clone());
+    vm.diagnostics = Box::new(output.clone());
+    builtins::add_all(&mut vm.dictionary);
+    vm.handle_errors = false;
+    vm.optimize = optimize;
+
+    let results = lines
+        .iter()
+        .map(|line| {
+            let result = vm.eval(line);
+            if result.is_err() {
+                vm.input_buffer.clear();
+                vm.state = State::Interpret;
+            }
+            result
+        })
+        .collect();
+    let stack = vm.value_stack.iter().cloned().collect();
+    (output.take(), stack, results)
+}
+
+fn assert_same(lines: &[&str]) {
+    let optimized = run(lines, true);
+    let plain = run(lines, false);
+    assert_eq!(optimized, plain, "{:?}", lines);
+}
+
+#[test]
+fn constant_folding() {
+    assert_same(&[": f 2 3 + 4 * 1 - ;", "f f * ."]);
+    assert_same(&[": f 1 2 3 + + dup + 1 + ;", "f ."]);
+}
+
+#[test]
+fn superinstructions() {
+    assert_same(&[": f dup + 1 + 1 - ;", "21 f ."]);
+    assert_same(&[": f 1 - ;", "f", "5 f ."]);
+    assert_same(&[": f dup + ;", "f", "s\" x\" drop f ."]);
+    assert_same(&[": f 1 + ;", "s\" x\" drop f ."]);
+}
+
+#[test]
+fn constant_branches() {
+    assert_same(&[": f 0 if 1 else 2 then ;", "f ."]);
+    assert_same(&[": f 1 if 1 else 2 then ;", "f ."]);
+    assert_same(&[": f 5 if .\" yes\" then 0 if .\" no\" then ;", "f"]);
+    assert_same(&[": f dup if 0 if 1 then 2 else 3 then ;", "0 f .", "4 f . ."]);
+}
+
+#[test]
+fn loops_and_branches() {
+    assert_same(&[": f 0 do i . loop ;", "5 f", "0 f"]);
+    assert_same(&[": f 10 0 do i 1 + . 3 +loop ;", "f"]);
+    assert_same(&[": f 0 10 do i . 2 -loop ;", "f"]);
+    assert_same(&[": f 3 0 do 2 0 do i j + 1 - . loop loop ;", "f"]);
+    assert_same(&[": f 0 swap 0 do i 2 = if 1 + then loop ;", "5 f ."]);
+}
+
+#[test]
+fn inlining() {
+    assert_same(&[": sq dup * ;", ": f sq sq ;", "3 f ."]);
+    assert_same(&[": inc 1 + ;", ": f 0 swap 0 do inc loop ;", "10 f ."]);
+    assert_same(&[
+        ": pos? dup if 1 else 0 then ;",
+        ": f pos? swap pos? ;",
+        "0 3 f . . . .",
+    ]);
+    assert_same(&[": two 1 1 + ;", ": f two two * two + ;", "f ."]);
+    assert_same(&[": sq dup * ;", ": f sq ;", "f"]);
+}
+
+#[test]
+fn recursion() {
+    assert_same(&[": countdown ?dup if dup . 1- recurse then ;", "5 countdown"]);
+    assert_same(&[
+        ": down dup . 1- ?dup if recurse then ;",
+        ": f down ;",
+        "4 f",
+    ]);
+}
+
+#[test]
+fn scripts() {
+    let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    for script in ["99bottles.fr", "basics.fr"] {
+        let source = std::fs::read_to_string(tests.join(script)).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        assert_same(&lines);
+    }
+}
+
+#[test]
+fn switch() {
+    let mut vm = VM::new_null();
+    builtins::add_all(&mut vm.dictionary);
+    vm.eval(": sq dup * ; : f 2 sq 1 + ;").unwrap();
+    assert_eq!(
+        vm.dictionary.get("f").unwrap()[..],
+        [
+            Cell::Data(Variable::Int(2)),
+            Cell::Inlined("sq".into(), vm.dictionary.get("sq").unwrap(), 3),
+            Cell::Exec(builtins::dup),
+            Cell::Exec(builtins::times),
+            Cell::Exec(builtins::runtime_one_plus),
+        ]
+    );
+
+    vm.optimize = false;
+    vm.eval(": g 2 sq 1 + ;").unwrap();
+    assert_eq!(vm.dictionary.get("g").unwrap().len(), 4);
+}
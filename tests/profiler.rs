@@ -0,0 +1,82 @@
+//! calls and times measured by the profiler
+
+use frust::{builtins::WordProfile, *};
+
+fn profile(definitions: &str, line: &str) -> (VM, Vec<WordProfile>) {
+    let mut vm = VM::new_null();
+    builtins::add_all(&mut vm.dictionary);
+    vm.eval(definitions).unwrap();
+    vm.profiler.enabled = true;
+    vm.eval(line).unwrap();
+    vm.profiler.enabled = false;
+    let report = vm.profiler.report(&vm.dictionary);
+    (vm, report)
+}
+
+fn word<'a>(report: &'a [WordProfile], name: &str) -> &'a WordProfile {
+    report
+        .iter()
+        .find(|word| word.name == name)
+        .unwrap_or_else(|| panic!("{} not profiled", name))
+}
+
+#[test]
+fn recursion_counts_every_call_but_the_time_once() {
+    let (_, report) = profile(": down ?dup if 1- recurse then ;", "50 down");
+    let down = word(&report, "down");
+    assert_eq!(down.calls, 51);
+    assert_eq!(report[0].name, "down");
+    assert!(down.inclusive >= down.exclusive);
+    assert_eq!(word(&report, "1-").calls, 50);
+}
+
+#[test]
+fn tail_calls_are_calls() {
+    let definitions = ": inner 1 drop ; : outer 0 drop inner ;";
+    let (vm, report) = profile(definitions, "outer outer");
+    assert_eq!(word(&report, "outer").calls, 2);
+    assert_eq!(word(&report, "inner").calls, 2);
+    let collapsed = vm.profiler.collapsed(&vm.dictionary);
+    // the tail call replaced `outer`
+    assert!(collapsed
+        .lines()
+        .any(|line| line.starts_with("inner;drop ")));
+}
+
+#[test]
+fn interpreted_and_compiled_natives_are_one_word() {
+    let (_, report) = profile(": f dup drop ;", "1 f dup drop drop");
+    assert_eq!(word(&report, "dup").calls, 2);
+    assert_eq!(word(&report, "drop").calls, 3);
+    assert_eq!(word(&report, "f").calls, 1);
+}
+
+#[test]
+fn errors_end_the_running_words() {
+    let mut vm = VM::new_null();
+    builtins::add_all(&mut vm.dictionary);
+    vm.handle_errors = false;
+    vm.eval(": inner 0 drop drop ; : outer inner 1 ;").unwrap();
+    vm.profiler.enabled = true;
+    assert_eq!(vm.eval("outer"), Err(Error::Stack));
+    vm.eval("1 outer").unwrap();
+
+    let collapsed = vm.profiler.collapsed(&vm.dictionary);
+    // the failed run didn't leave `outer` open, the second one isn't below it
+    assert!(!collapsed.contains("outer;outer"));
+    assert_eq!(word(&vm.profiler.report(&vm.dictionary), "outer").calls, 2);
+}
+
+#[test]
+fn loops_show_do() {
+    let (vm, _) = profile(": f 3 0 do loop ;", "f");
+    assert!(vm.profiler.collapsed(&vm.dictionary).contains("f;do "));
+}
+
+#[test]
+fn clear_forgets_everything() {
+    let (mut vm, _) = profile(": f 1 drop ;", "f");
+    vm.profiler.clear();
+    assert!(vm.profiler.report(&vm.dictionary).is_empty());
+    assert_eq!(vm.profiler.collapsed(&vm.dictionary), "");
+}
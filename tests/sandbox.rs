@@ -0,0 +1,109 @@
+//! denied words and quotas of `VM::sandbox`
+
+use frust::*;
+
+mod common;
+
+#[test]
+fn untrusted_scripts_cant_touch_files_or_leave() {
+    let mut vm = VM::new_null();
+    vm.handle_errors = false;
+    vm.sandbox = Sandbox::untrusted();
+    for set in [
+        builtins::WordSet::Core,
+        builtins::WordSet::Tools,
+        builtins::WordSet::File,
+    ] {
+        vm.add_word_set(set);
+    }
+    assert!(vm.dictionary.get("open-file").is_err());
+    assert!(vm.dictionary.get(".s").is_ok());
+
+    for line in ["s\" /etc/passwd\" r/o open-file", "bye", "debug dup"] {
+        assert!(matches!(vm.eval(line), Err(Error::Denied(_))), "{}", line);
+        vm.cancel();
+    }
+    assert!(matches!(vm.eval(": f 0 bye ;"), Err(Error::Denied(_))));
+    assert!(vm.dictionary.get("f").is_err());
+    vm.cancel();
+    assert_eq!(vm.eval("1 2 + .s drop"), Ok(()));
+}
+
+#[test]
+fn words_added_before_are_denied_on_lookup() {
+    let mut vm = common::vm(&Capture::new());
+    vm.handle_errors = false;
+    vm.sandbox.deny_set(builtins::WordSet::Tools);
+    vm.sandbox.allow("bye");
+
+    assert_eq!(vm.eval(".s"), Err(Error::Denied(".s".to_owned())));
+    assert_eq!(
+        vm.eval("PROFILE dup"),
+        Err(Error::Denied("PROFILE".to_owned()))
+    );
+    vm.cancel();
+    assert_eq!(vm.eval("bye"), Err(Error::Bye(0)));
+}
+
+#[test]
+fn words_compiled_before_cant_run_denied_natives() {
+    let mut vm = common::vm(&Capture::new());
+    vm.handle_errors = false;
+    vm.eval(": f s\" x\" delete-file ;").unwrap();
+    vm.sandbox = Sandbox::untrusted();
+
+    assert_eq!(vm.eval("f"), Err(Error::Denied("delete-file".to_owned())));
+    // the name is still there, `delete-file` never ran
+    assert_eq!(vm.value_stack.len(), 2);
+}
+
+#[test]
+fn breaches_end_execution_and_empty_the_stacks() {
+    let mut vm = common::vm(&Capture::new());
+    vm.handle_errors = false;
+    vm.sandbox.max_return_stack = Some(3);
+    assert_eq!(
+        vm.eval("1 : f 1 0 do 1 0 do i drop loop loop ; f"),
+        Err(Error::ReturnStackFull)
+    );
+    assert!(vm.value_stack.is_empty() && vm.return_stack.is_empty());
+
+    vm.sandbox.max_memory = Some(1 << 16);
+    assert_eq!(vm.eval(": g 0 do s\" some text\" loop ; 10 g"), Ok(()));
+    assert_eq!(vm.eval("100000 g"), Err(Error::OutOfMemory));
+    assert!(vm.value_stack.is_empty());
+    // reads are checked before anything is read
+    assert_eq!(vm.eval("1000000 accept"), Err(Error::OutOfMemory));
+}
+
+#[test]
+fn definitions_over_the_quota_are_not_added() {
+    let mut vm = common::vm(&Capture::new());
+    vm.handle_errors = false;
+    vm.sandbox.max_dictionary = Some(vm.dictionary.cells() + 2);
+
+    assert_eq!(vm.eval("1 : two 1 2 ;"), Ok(()));
+    assert_eq!(vm.eval(": three 1 2 3 ;"), Err(Error::DictionaryFull));
+    assert!(vm.dictionary.get("three").is_err());
+    // replacing a word only counts the difference
+    assert_eq!(vm.eval(": two 2 ;"), Ok(()));
+    // only the dictionary was too big, the stack stays
+    assert_eq!(vm.value_stack.len(), 1);
+}
+
+#[test]
+fn literals_count_their_bytes() {
+    let mut vm = common::vm(&Capture::new());
+    vm.handle_errors = false;
+    vm.sandbox.max_memory = Some(1 << 16);
+    let text = "x".repeat(1 << 10);
+
+    assert_eq!(vm.eval(&format!(": short .\" {}\" ;", text)), Ok(()));
+    let long = format!(": long .\" {}\" ;", text.repeat(1 << 6));
+    assert_eq!(vm.eval(&long), Err(Error::OutOfMemory));
+    assert!(vm.dictionary.get("long").is_err());
+
+    vm.sandbox.max_string = Some(100);
+    let long = format!(": long s\" {}\" ;", text);
+    assert_eq!(vm.eval(&long), Err(Error::StringTooLong));
+}
@@ -0,0 +1,117 @@
+//! VMs on worker threads
+
+use frust::*;
+use std::{cell::RefCell, rc::Rc, thread};
+
+mod common;
+
+#[test]
+fn scripts_run_concurrently() {
+    let workers: Vec<_> = (1..=8)
+        .map(|n| {
+            let output = Capture::new();
+            let mut vm = common::vm(&output);
+            let script = format!(": sum 0 swap 1 + 1 do i + loop ; {} 1000 * sum .", n);
+            let worker = thread::spawn(move || {
+                vm.eval(&script).unwrap();
+                vm.flush().unwrap();
+                vm
+            });
+            (n, output, worker)
+        })
+        .collect();
+
+    for (n, output, worker) in workers {
+        let vm = worker.join().unwrap();
+        let count = n * 1000;
+        assert_eq!(output.take(), format!("{} ", count * (count + 1) / 2));
+        assert!(vm.value_stack.is_empty());
+        assert!(vm.dictionary.get("sum").is_ok());
+    }
+}
+
+#[test]
+fn closures_share_state_across_threads() {
+    let total = std::sync::Arc::new(std::sync::atomic::AtomicI64::new(0));
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let mut vm = common::vm(&Capture::new());
+            let total = total.clone();
+            vm.dictionary.add_closure("add", move |vm| {
+                let Variable::Int(n) = vm.value_stack.pop()? else {
+                    return Err(Error::Type);
+                };
+                total.fetch_add(n, std::sync::atomic::Ordering::Relaxed);
+                Ok(())
+            });
+            thread::spawn(move || vm.eval(": f 100 0 do i add loop ; f"))
+        })
+        .collect();
+
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), Ok(()));
+    }
+    assert_eq!(total.load(std::sync::atomic::Ordering::Relaxed), 4 * 4950);
+}
+
+#[test]
+fn vms_call_the_same_closure_at_the_same_time() {
+    let mut calls = 0;
+    let counter = Closure::new(move |vm| {
+        calls += 1;
+        vm.value_stack.push(calls);
+        thread::yield_now();
+        Ok(())
+    });
+    let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let mut vm = common::vm(&Capture::new());
+            vm.handle_errors = false;
+            vm.dictionary.add("count", Cell::Closure(counter.clone()));
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                vm.eval(": f 1000 0 do count drop loop ; f")
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), Ok(()));
+    }
+    let mut vm = common::vm(&Capture::new());
+    vm.dictionary.add("count", Cell::Closure(counter));
+    assert_eq!(vm.call::<(), i64>("count", ()), Ok(2001));
+}
+
+#[test]
+fn local_output_fails_on_other_threads() {
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let shared = Shared::default();
+    let mut vm = VM::new(std::io::empty(), Local::new(shared.clone()));
+    builtins::add_core(&mut vm.dictionary);
+    vm.handle_errors = false;
+
+    let mut vm = thread::spawn(move || {
+        assert!(vm.eval("1 . cr").and_then(|_| vm.flush()).is_err());
+        vm
+    })
+    .join()
+    .unwrap();
+    vm.eval("2 . cr").unwrap();
+    vm.flush().unwrap();
+    assert_eq!(String::from_utf8_lossy(&shared.0.borrow()), "1 \n2 \n");
+}